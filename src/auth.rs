@@ -0,0 +1,179 @@
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use axum::http::{header, HeaderMap};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+const TOKEN_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+#[derive(Serialize, Deserialize)]
+struct TokenClaims {
+    // user id
+    sub: String,
+    // expiry in seconds since the unix epoch
+    exp: u64,
+}
+
+// Argon2id hash in the PHC string format, the salt is stored in the hash.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes()).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|_| "Could not hash password".to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn validate_user_name(name: &str) -> Result<(), String> {
+    let valid_chars = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match (3..=32).contains(&name.len()) && valid_chars {
+        true => Ok(()),
+        false => Err("User name must be 3 to 32 letters, digits, '_' or '-'".to_string()),
+    }
+}
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    match (MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&password.chars().count()) {
+        true => Ok(()),
+        false => Err(format!("Password must be {} to {} characters long", MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH)),
+    }
+}
+
+// Issues and checks HS256 signed JWTs, so sessions need no state on the server.
+pub struct TokenSigner {
+    key: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(key: Vec<u8>) -> TokenSigner {
+        TokenSigner { key }
+    }
+
+    // Key from AUTH_SECRET, otherwise a random key and tokens end with the process.
+    pub fn new_from_env() -> TokenSigner {
+        match std::env::var("AUTH_SECRET") {
+            Ok(secret) if !secret.is_empty() => TokenSigner::new(secret.into_bytes()),
+            _ => {
+                println!("AUTH_SECRET is not set, tokens will not survive a restart");
+                let key = [*Uuid::new_v4().as_bytes(), *Uuid::new_v4().as_bytes()].concat();
+                TokenSigner::new(key)
+            },
+        }
+    }
+
+    fn mac(&self, signing_input: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(signing_input.as_bytes());
+        mac
+    }
+
+    pub fn issue_token(&self, user_id: &str, now: SystemTime) -> String {
+        let exp = (now + TOKEN_LIFETIME).duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+        let claims = serde_json::to_string(&TokenClaims { sub: user_id.to_string(), exp }).unwrap();
+        let signing_input = format!(
+            "{}.{}",
+            Base64UrlUnpadded::encode_string(TOKEN_HEADER.as_bytes()),
+            Base64UrlUnpadded::encode_string(claims.as_bytes()),
+        );
+        let signature = self.mac(&signing_input).finalize().into_bytes();
+        format!("{}.{}", signing_input, Base64UrlUnpadded::encode_string(&signature))
+    }
+
+    // User id of a token signed with this key that has not expired.
+    pub fn verify_token(&self, token: &str, now: SystemTime) -> Result<String, String> {
+        let invalid = || "Invalid token".to_string();
+        let (signing_input, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, claims) = signing_input.split_once('.').ok_or_else(invalid)?;
+        let signature = Base64UrlUnpadded::decode_vec(signature).map_err(|_| invalid())?;
+        self.mac(signing_input).verify_slice(&signature).map_err(|_| invalid())?;
+
+        if Base64UrlUnpadded::decode_vec(header).map_err(|_| invalid())? != TOKEN_HEADER.as_bytes() {
+            return Err(invalid());
+        }
+        let claims = Base64UrlUnpadded::decode_vec(claims).map_err(|_| invalid())?;
+        let claims: TokenClaims = serde_json::from_slice(&claims).map_err(|_| invalid())?;
+        if claims.exp <= now.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs()) {
+            return Err("Token expired".to_string());
+        }
+        Ok(claims.sub)
+    }
+
+    // User of the "Authorization: Bearer <token>" header.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<String, String> {
+        let token = headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or("Not logged in".to_string())?;
+        self.verify_token(token.trim(), SystemTime::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use super::*;
+
+    #[test]
+    fn password_hash_verifies_only_the_same_password() {
+        let password_hash = hash_password("correct horse").unwrap();
+        assert!(password_hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &password_hash));
+        assert!(!verify_password("wrong horse", &password_hash));
+        assert_ne!(hash_password("correct horse").unwrap(), password_hash);
+    }
+
+    #[test]
+    fn user_names_and_passwords_are_validated() {
+        assert!(validate_user_name("alice_99").is_ok());
+        assert!(validate_user_name("al").is_err());
+        assert!(validate_user_name("alice smith").is_err());
+        assert!(validate_password("short").is_err());
+        assert!(validate_password("long enough").is_ok());
+    }
+
+    #[test]
+    fn signed_token_authenticates_its_user() {
+        let token_signer = TokenSigner::new(b"secret".to_vec());
+        let token = token_signer.issue_token("1", SystemTime::now());
+        assert_eq!(token.split('.').count(), 3);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(token_signer.authenticate(&headers), Err("Not logged in".to_string()));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).unwrap());
+        assert_eq!(token_signer.authenticate(&headers), Ok("1".to_string()));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer nope"));
+        assert!(token_signer.authenticate(&headers).is_err());
+    }
+
+    #[test]
+    fn forged_or_expired_tokens_are_rejected() {
+        let token_signer = TokenSigner::new(b"secret".to_vec());
+        let now = SystemTime::now();
+        let token = token_signer.issue_token("1", now);
+
+        let other_signer = TokenSigner::new(b"other secret".to_vec());
+        assert!(other_signer.verify_token(&token, now).is_err());
+
+        // claims of another user with the original signature
+        let parts: Vec<&str> = token.split('.').collect();
+        let claims = Base64UrlUnpadded::encode_string(br#"{"sub":"2","exp":99999999999}"#);
+        let forged = format!("{}.{}.{}", parts[0], claims, parts[2]);
+        assert_eq!(token_signer.verify_token(&forged, now), Err("Invalid token".to_string()));
+
+        assert_eq!(token_signer.verify_token(&token, now + TOKEN_LIFETIME), Err("Token expired".to_string()));
+    }
+}
@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::types::{ToSql, FromSql, Type, IsNull, to_sql_checked};
+use tokio_postgres::types::private::BytesMut;
+use uuid::Uuid;
+
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+// author of messages by spectators who did not log in
+pub const ANONYMOUS_SPECTATOR: &str = "anonymous";
+
+// Players and spectators chat in separate rooms of a game.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRoom {
+    Players,
+    Spectators,
+}
+
+impl ChatRoom {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRoom::Players => "players",
+            ChatRoom::Spectators => "spectators",
+        }
+    }
+}
+
+impl ToSql for ChatRoom {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.extend_from_slice(self.as_str().as_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool
+    where
+        Self: Sized,
+    {
+        ty == &Type::VARCHAR || ty == &Type::TEXT
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for ChatRoom {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        match std::str::from_utf8(raw)? {
+            "players" => Ok(ChatRoom::Players),
+            "spectators" => Ok(ChatRoom::Spectators),
+            _ => Err("Unknown chat room".into()),
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty == &Type::VARCHAR || ty == &Type::TEXT
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub game_id: Uuid,
+    pub room: ChatRoom,
+    pub user_id: String,
+    pub message: String,
+    // milliseconds since the unix epoch
+    pub sent_at_ms: u64,
+}
+
+impl ChatMessage {
+    // Trims the text and rejects empty or too long messages.
+    pub fn new(game_id: Uuid, room: ChatRoom, user_id: String, message: &str) -> Result<ChatMessage, String> {
+        let message = message.trim();
+        if message.is_empty() {
+            return Err("Chat message is empty".to_string());
+        }
+        if message.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+            return Err(format!("Chat message is longer than {} characters", MAX_CHAT_MESSAGE_LENGTH));
+        }
+        let sent_at_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as u64);
+        Ok(ChatMessage { game_id, room, user_id, message: message.to_string(), sent_at_ms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_message_is_trimmed_and_limited() {
+        let game_id = Uuid::new_v4();
+        let chat_message = ChatMessage::new(game_id, ChatRoom::Players, "alice".to_string(), "  good luck ").unwrap();
+        assert_eq!(chat_message.message, "good luck");
+
+        assert!(ChatMessage::new(game_id, ChatRoom::Players, "alice".to_string(), "   ").is_err());
+        let long_message = "a".repeat(MAX_CHAT_MESSAGE_LENGTH + 1);
+        assert!(ChatMessage::new(game_id, ChatRoom::Spectators, "carol".to_string(), &long_message).is_err());
+    }
+}
@@ -8,14 +8,20 @@ use std::sync::Arc;
 use crate::chess_engine::piece::PieceEnum;
 use crate::chess_engine::coordinates::Coordinates;
 use crate::chess_engine::color::{ActiveColor, Color};
+use crate::chess_engine::position::{Position, PieceKind, color_index, square_from_coordinates};
+use crate::chess_engine::san;
 use pleco::{Board as StockfishBoard, BitMove};
 use crate::game_status::GameStatus;
 use crate::game_end_condition::GameEndCondition;
 
+pub const INITIAL_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 #[derive(Debug, Clone)]
 pub struct Board {
     id: Option<i32>,
     fen: String,
+    // position the game started from
+    initial_fen: String,
     pieces: HashMap<Coordinates, Option<PieceEnum>>,
     possible_moves: HashMap<String, (String, Vec<String>)>,
     active_color: ActiveColor,
@@ -37,6 +43,34 @@ pub struct Board {
     b_king_in_check: bool,
     moves_count: i32,
     moves_history: String,
+    last_move_san: Option<String>,
+    position_history: Vec<u64>,
+    move_stack: Vec<MoveUndo>,
+}
+
+// Everything a move changes that cannot be recomputed from the position after
+// it, kept so that the move can be taken back.
+#[derive(Debug, Clone)]
+struct MoveUndo {
+    move_from: Coordinates,
+    move_to: Coordinates,
+    // the piece as it was before the move, a pawn in case of a promotion
+    moved_piece: PieceEnum,
+    promotion_piece: Option<String>,
+    // the square differs from move_to for en passant captures
+    captured_piece: Option<(Coordinates, PieceEnum)>,
+    rook_move: Option<(Coordinates, Coordinates)>,
+    active_color: ActiveColor,
+    castle_options: String,
+    en_passant_square: String,
+    half_move_clock: i32,
+    full_move_number: i32,
+    w_king_square: Option<Coordinates>,
+    b_king_square: Option<Coordinates>,
+    moves_count: i32,
+    moves_history_length: usize,
+    last_move_san: Option<String>,
+    position_history_length: usize,
 }
 
 impl Board {
@@ -50,6 +84,7 @@ impl Board {
         let mut board = Board {
             id: None,
             fen: fen.clone(),
+            initial_fen: fen.clone(),
             pieces: HashMap::new(),
             possible_moves: HashMap::new(),
             number_of_columns: n_of_columns,
@@ -78,19 +113,18 @@ impl Board {
             b_king_in_check: false,
             moves_count: 0,
             moves_history: String::new(),
+            last_move_san: None,
+            position_history: Vec::new(),
+            move_stack: Vec::new(),
         };
         board.create_pieces_from_fen(fen);
 
         let color = board.active_color.clone();
-        board.generate_possible_moves(&color, &true);
-        board.generate_possible_moves(&color.next(), &true);
-        // println!("board possible moves: {:?}", board.possible_moves);
+        board.generate_possible_moves();
+        board.position_history.push(board.to_position().repetition_key());
 
         board.update_check_status(&color);
         board.update_check_status(&color.next());
-
-        board.generate_castle_moves(&color);
-        // println!("board possible moves: {:?}", board.possible_moves);
         println!("white king in check: {}, black king in check: {}", board.w_king_in_check, board.b_king_in_check);
         board
     }
@@ -98,6 +132,7 @@ impl Board {
     pub fn new_from_db(
         id: i32,
         fen: String,
+        initial_fen: Option<String>,
         pieces: HashMap<Coordinates, Option<PieceEnum>>,
         active_color: char,
         castle_options: String,
@@ -110,8 +145,7 @@ impl Board {
         rows: String,
         moves_count: i32,
         moves_history: &str,
-    ) -> Board {
-        //todo: update to get w_king_square and b_king_square from pieces
+    ) -> Result<Board, String> {
         let active_color_enum = match active_color {
             'b' => ActiveColor::Black,
             _ => ActiveColor::White,
@@ -119,6 +153,8 @@ impl Board {
         let mut board = Board {
             id: Some(id),
             fen,
+            // boards stored before it was kept started from the initial position
+            initial_fen: initial_fen.unwrap_or(INITIAL_FEN.to_string()),
             pieces,
             possible_moves: HashMap::new(),
             active_color: active_color_enum,
@@ -146,6 +182,9 @@ impl Board {
             b_king_in_check: false,
             moves_count,
             moves_history: moves_history.to_string(),
+            last_move_san: None,
+            position_history: Vec::new(),
+            move_stack: Vec::new(),
         };
 
         for row in board.rows.chars() {
@@ -157,17 +196,63 @@ impl Board {
             }
         }
 
+        for (coordinates, piece) in board.pieces.iter() {
+            match piece.as_ref().map(|piece| piece.get_symbol()).as_deref() {
+                Some("K") => board.w_king_square = Some(coordinates.clone()),
+                Some("k") => board.b_king_square = Some(coordinates.clone()),
+                _ => {},
+            }
+        }
+
+        if Board::is_legacy_moves_history(&board.moves_history) {
+            let (moves_history, last_move_san) = board.convert_legacy_moves_history()?;
+            board.moves_history = moves_history;
+            board.last_move_san = last_move_san;
+        }
+
         let color = board.active_color.clone();
         board.castle_options = board.get_castle_options_by_rook_starting_squares();
-        board.generate_possible_moves(&color, &true);
-        board.generate_possible_moves(&color.next(), &true);
+        board.generate_possible_moves();
+        board.position_history.push(board.to_position().repetition_key());
 
         board.update_check_status(&color);
         board.update_check_status(&color.next());
 
-        board.generate_castle_moves(&color);
+        Ok(board)
+    }
 
-        board
+    // Boards stored before the history was kept in SAN list their moves by
+    // squares, with the move number before White's move: "1 e2e4 e7e5  2 g1f3 ".
+    fn is_legacy_moves_history(moves_history: &str) -> bool {
+        moves_history.split_whitespace().next()
+            .is_some_and(|token| token.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    // Replays the legacy moves from the initial position to write the history and
+    // the last move in SAN.
+    fn convert_legacy_moves_history(&self) -> Result<(String, Option<String>), String> {
+        let mut board = Board::new_from_fen(
+            self.columns.clone(),
+            self.number_of_columns,
+            self.rows.clone(),
+            self.number_of_rows,
+            self.initial_fen.clone(),
+        );
+        let moves = self.moves_history.split_whitespace()
+            .filter(|token| !token.chars().all(|c| c.is_ascii_digit()));
+        for legacy_move in moves {
+            let (move_from, move_to) = legacy_move.split_at(legacy_move.len() / 2);
+            // the promotion piece was not stored, pawns are promoted to a queen
+            let queen = match board.active_color {
+                ActiveColor::White => "Q",
+                ActiveColor::Black => "q",
+            };
+            if !board.make_move_string(move_from.to_string(), move_to.to_string(), None)
+                && !board.make_move_string(move_from.to_string(), move_to.to_string(), Some(queen.to_string())) {
+                return Err(format!("Could not convert move {} of the legacy move history", legacy_move));
+            }
+        }
+        Ok((board.moves_history, board.last_move_san))
     }
 
     pub fn board_to_fen(&self) -> String {
@@ -283,6 +368,10 @@ impl Board {
         self.fen.clone()
     }
 
+    pub fn get_initial_fen(&self) -> String {
+        self.initial_fen.clone()
+    }
+
     pub fn get_pieces_dict(&self) -> HashMap<Coordinates, Option<PieceEnum>> {
         self.pieces.clone()
     }
@@ -373,6 +462,32 @@ impl Board {
         self.moves_history.as_str()
     }
 
+    // Number of half-moves made, taken from the move history.
+    pub fn get_ply_count(&self) -> usize {
+        self.moves_history.split_whitespace().filter(|token| !token.ends_with('.')).count()
+    }
+
+    pub fn get_last_move_san(&self) -> Option<String> {
+        self.last_move_san.clone()
+    }
+
+    // Number of times the current position has occurred, including now.
+    pub fn get_repetition_count(&self) -> usize {
+        match self.position_history.last() {
+            Some(key) => self.position_history.iter().filter(|k| *k == key).count(),
+            None => 0,
+        }
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.get_repetition_count() >= 3
+    }
+
+    // Fifty moves by each side without a pawn move or a capture.
+    pub fn is_fifty_move_rule(&self) -> bool {
+        self.half_move_clock >= 100
+    }
+
     pub fn make_move(
         &mut self,
         move_from: &Coordinates,
@@ -381,36 +496,68 @@ impl Board {
         promotion_piece: Option<String>
     ) -> bool {
         // todo: update board in database
-        {
-            if let Some(piece_option) = self.pieces.get(&move_from) {
-                match piece_option {
-                    Some(piece) => {
-                        let a = piece.get_possible_moves();
-                        if piece.get_color() != self.active_color.to_char()
-                            || !piece.get_possible_moves().contains(&move_to.to_string()) {
+        let promotion_symbol = match self.pieces.get(move_from) {
+            Some(Some(piece)) => {
+                if piece.get_color() != self.active_color.to_char()
+                    || !piece.get_possible_moves().contains(&move_to.to_string()) {
+                    return false;
+                }
+
+                // pawn promotion, validated before the board is modified
+                let promotion_pieces = match piece.get_symbol().as_str() {
+                    "P" if move_to.row == 7 => Some(["Q", "R", "B", "N"]),
+                    "p" if move_to.row == 0 => Some(["q", "r", "b", "n"]),
+                    _ => None,
+                };
+                match (promotion_pieces, promotion_piece) {
+                    (None, _) => None,
+                    (Some(promotion_pieces), Some(promotion_piece)) => {
+                        if promotion_pieces.contains(&promotion_piece.as_str()) {
+                            promotion_piece.chars().nth(0)
+                        } else {
                             return false;
                         }
                     },
-                    _ => return false,
+                    (Some(_), None) => return false,
                 }
-            }
-        }
+            },
+            _ => return false,
+        };
+
+        let position = self.to_position();
+        let promotion_kind = promotion_symbol.and_then(PieceKind::new_from_char).map(|(kind, _)| kind);
+        let san = match position.move_from_squares(move_from, move_to, promotion_kind) {
+            Some(piece_move) => san::move_to_san(&position, &piece_move),
+            None => format!("{}{}", move_from.to_string(), move_to.to_string()),
+        };
 
-        if let Some(piece_option) = self.pieces.get_mut(&move_from) {
+        // pawn moves and captures reset the fifty-move rule counter
+        let resets_half_move_clock = matches!(self.pieces.get(move_to), Some(Some(_)))
+            || matches!(self.pieces.get(move_from), Some(Some(piece)) if ["P", "p"].contains(&piece.get_symbol().as_str()));
+
+        let active_color = self.active_color.clone();
+        let castle_options = self.castle_options.clone();
+        let en_passant_square = self.en_passant_square.clone();
+        let w_king_square = self.w_king_square.clone();
+        let b_king_square = self.b_king_square.clone();
+        let moves_count = self.moves_count;
+        let moves_history_length = self.moves_history.len();
+        let last_move_san = self.last_move_san.clone();
+        let mut moved_piece: Option<PieceEnum> = None;
+        let mut captured_piece: Option<(Coordinates, PieceEnum)> = None;
+        let mut rook_move: Option<(Coordinates, Coordinates)> = None;
+
+        if let Some(piece_option) = self.pieces.get_mut(move_from) {
             match piece_option.take() {
                 Some(mut piece) => {
-                    if piece.get_color() != self.active_color.to_char()
-                        || !piece.get_possible_moves().contains(&move_to.to_string()) {
-                        return false;
-                    }
-
+                    moved_piece = Some(piece.clone());
                     // king moves
                     // the king either moves one square or castles
                     // both cases disable castles move for this king
                     if piece.get_symbol() == "K" && piece.get_color() == 'w' {
                         self.w_king_square = Some(move_to.clone());
                         if (move_to.column - move_from.column).abs() == 2 {
-                            self.castle_rook(&ActiveColor::new_from_char('w').unwrap(), move_to);
+                            rook_move = Some(self.castle_rook(&ActiveColor::new_from_char('w').unwrap(), move_to));
                         }
                         self.castle_options = self.castle_options.chars().filter(|c| !c.is_uppercase()).collect();
                     }
@@ -418,7 +565,7 @@ impl Board {
                     if piece.get_symbol() == "k" && piece.get_color() == 'b' {
                         self.b_king_square = Some(move_to.clone());
                         if (move_to.column - move_from.column).abs() == 2 {
-                            self.castle_rook(&ActiveColor::new_from_char('b').unwrap(), move_to);
+                            rook_move = Some(self.castle_rook(&ActiveColor::new_from_char('b').unwrap(), move_to));
                         }
                         self.castle_options = self.castle_options.chars().filter(|c| !c.is_lowercase()).collect();
                     }
@@ -428,66 +575,32 @@ impl Board {
                         self.castle_options = self.update_castle_options_after_rook_move(move_from, move_to);
                     }
 
-                    match self.pieces.get(move_to) {
-                        Some(piece) => {
-                            match piece {
-                                Some(piece) => {
-                                    if ["R", "r"].contains(&piece.get_symbol().as_str()) {
-                                        self.castle_options = self.update_castle_options_after_rook_move(move_from, move_to);
-                                    }
-                                },
-                                _ => {},
-                            }
-                        },
-                        _ => {},
+                    if let Some(Some(captured_piece)) = self.pieces.get(move_to) {
+                        if ["R", "r"].contains(&captured_piece.get_symbol().as_str()) {
+                            self.castle_options = self.update_castle_options_after_rook_move(move_from, move_to);
+                        }
                     }
 
                     // pawn moves
                     if ["P", "p"].contains(&piece.get_symbol().as_str()) {
-                        // pawn promotion
-                        if move_to.row == 7 && piece.get_symbol().as_str() == "P" {
-                            match promotion_piece {
-                                Some(promotion_piece) => {
-                                    if ["Q", "R", "B", "N"].contains(&promotion_piece.as_str()) {
-                                        piece = PieceEnum::new(move_to.clone(), promotion_piece.chars().nth(0).unwrap());
-                                    } else {
-                                        return false;
-                                    }
-                                },
-                                _ => return false,
-                            }
-                        }
-
-                        else if move_to.row == 0 && piece.get_symbol().as_str() == "p" {
-                            match promotion_piece {
-                                Some(promotion_piece) => {
-                                    if ["q", "r", "b", "n"].contains(&promotion_piece.as_str()) {
-                                        piece = PieceEnum::new(move_to.clone(), promotion_piece.chars().nth(0).unwrap());
-                                    } else {
-                                        return false;
-                                    }
-                                },
-                                _ => return false,
-                            }
+                        if let Some(promotion_symbol) = promotion_symbol {
+                            piece = PieceEnum::new(move_to.clone(), promotion_symbol);
                         }
 
                         // pawn captures en passant
-                        println!("en passant square: {}, move to: {}", self.en_passant_square, move_to.to_string());
                         if move_to.to_string() == self.en_passant_square {
-                            let move_to_clone = move_to.clone();
                             let direction = match self.active_color {
                                 ActiveColor::White => -1,
                                 ActiveColor::Black => 1,
                             };
 
                             let coords = Coordinates::new_from_int(
-                                &move_to_clone.column,
-                                &(move_to_clone.row + direction),
-                            );
-                            self.pieces.insert(
-                                coords,
-                                None,
+                                &move_to.column,
+                                &(move_to.row + direction),
                             );
+                            if let Some(Some(pawn)) = self.pieces.insert(coords.clone(), None) {
+                                captured_piece = Some((coords, pawn));
+                            }
                         }
                         // en passant square
                         if move_from.row == 1 && move_to.row == 3 {
@@ -502,31 +615,58 @@ impl Board {
                         self.en_passant_square = "-".to_string();
                     }
 
-                    piece.set_coordinates(&move_to);
+                    piece.set_coordinates(move_to);
                     self.pieces.insert(move_from.clone(), None);
-                    self.pieces.insert(move_to.clone(), Some(piece));
-                    self.add_move_to_made_moves(move_from, move_to);
+                    if let Some(Some(captured)) = self.pieces.insert(move_to.clone(), Some(piece)) {
+                        captured_piece = Some((move_to.clone(), captured));
+                    }
+                    self.add_move_to_made_moves(&san);
                 },
                 _ => return false,
             }
         }
 
+        let position_history_length = self.position_history.len();
+        if let Some(moved_piece) = moved_piece {
+            self.move_stack.push(MoveUndo {
+                move_from: move_from.clone(),
+                move_to: move_to.clone(),
+                moved_piece,
+                promotion_piece: promotion_symbol.map(|symbol| symbol.to_string()),
+                captured_piece,
+                rook_move,
+                active_color,
+                castle_options,
+                en_passant_square,
+                half_move_clock: self.half_move_clock,
+                full_move_number: self.full_move_number,
+                w_king_square,
+                b_king_square,
+                moves_count,
+                moves_history_length,
+                last_move_san,
+                position_history_length,
+            });
+        }
+
+        self.half_move_clock = match resets_half_move_clock {
+            true => 0,
+            false => self.half_move_clock + 1,
+        };
+        if let ActiveColor::Black = self.active_color {
+            self.full_move_number += 1;
+        }
+
         if calculate_new_moves {
-            self.update_check_status(&self.active_color.clone());
             self.active_color = self.active_color.next();
 
             let color_clone = self.active_color.clone();
-            self.generate_possible_moves(&color_clone, &true);
-            self.generate_possible_moves(&color_clone.next(), &false);
+            self.generate_possible_moves();
             self.update_check_status(&color_clone);
-
-            self.generate_castle_moves(&color_clone);
-
-            let s = format!("{}{}", move_from.to_string(), move_to.to_string());
-            println!("make_move_str square: {}", s);
+            self.update_check_status(&color_clone.next());
+            self.position_history.push(self.to_position().repetition_key());
         }
         self.fen = self.board_to_fen();
-        println!("white king in check: {}, black king in check: {}", self.w_king_in_check, self.b_king_in_check);
         true
     }
 
@@ -546,6 +686,26 @@ impl Board {
         self.make_move(&move_from, &move_to, true, promotion_piece)
     }
 
+    pub fn make_move_san(&mut self, san: String) -> bool {
+        let position = self.to_position();
+        match san::san_to_move(&position, &san) {
+            Ok(piece_move) => {
+                let promotion_piece = piece_move.promotion
+                    .map(|kind| kind.to_char(position.get_side_to_move()).to_string());
+                self.make_move(
+                    &piece_move.get_from_coordinates(),
+                    &piece_move.get_to_coordinates(),
+                    true,
+                    promotion_piece,
+                )
+            },
+            Err(e) => {
+                println!("{}", e);
+                false
+            },
+        }
+    }
+
     pub fn make_move_chars(&mut self, move_from: (char, char), move_to: (char, char), promotion_piece: Option<String>) -> bool {
         self.make_move(
             &Coordinates::new_from_char(&move_from.0, &move_from.1),
@@ -555,40 +715,8 @@ impl Board {
         )
     }
 
-    pub fn make_move_without_move_validation(
-        &mut self,
-        move_from: &Coordinates,
-        move_to: &Coordinates,
-        update_color: bool,
-    ) {
-        match self.pieces.get_mut(&move_from) {
-            Some(piece) => {
-                match piece.take() {
-                    Some(mut piece) => {
-                        if piece.get_symbol() == "K" && piece.get_color() == 'w' {
-                            self.w_king_square = Some(move_to.clone());
-                        }
-
-                        if piece.get_symbol() == "k" && piece.get_color() == 'b' {
-                            self.b_king_square = Some(move_to.clone());
-                        }
-                        piece.set_coordinates(&move_to);
-                        self.pieces.insert(move_from.clone(), None);
-                        self.pieces.insert(move_to.clone(), Some(piece.clone()));
-                        if update_color {
-                            self.active_color = self.active_color.next();
-                        }
-                        self.generate_possible_moves(&self.active_color.clone(), &false);
-                    },
-                    _ => { },
-                }
-            }
-
-            _ => { },
-        };
-    }
-
-    pub fn castle_rook(&mut self, king_color: &ActiveColor, move_to: &Coordinates) {
+    // Moves the rook of a castling king, returns the rook's squares.
+    pub fn castle_rook(&mut self, king_color: &ActiveColor, move_to: &Coordinates) -> (Coordinates, Coordinates) {
         let rook_column: i8;
         let rook_new_column: i8;
         if move_to.column < (move_to.column - 7).abs() {
@@ -599,22 +727,67 @@ impl Board {
             rook_new_column = 5;
         }
 
-        match king_color {
-            ActiveColor::White => {
-                self.make_move_without_move_validation(
-                    &Coordinates::new_from_int(&rook_column, &0),
-                    &Coordinates::new_from_int(&rook_new_column, &0),
-                    false,
-                );
-            },
-            ActiveColor::Black => {
-                self.make_move_without_move_validation(
-                    &Coordinates::new_from_int(&rook_column, &7),
-                    &Coordinates::new_from_int(&rook_new_column, &7),
-                    false,
-                );
-            },
+        let row = match king_color {
+            ActiveColor::White => 0,
+            ActiveColor::Black => 7,
+        };
+
+        let rook_from = Coordinates::new_from_int(&rook_column, &row);
+        let rook_to = Coordinates::new_from_int(&rook_new_column, &row);
+        if let Some(Some(mut rook)) = self.pieces.insert(rook_from.clone(), None) {
+            rook.set_coordinates(&rook_to);
+            self.pieces.insert(rook_to.clone(), Some(rook));
         }
+        (rook_from, rook_to)
+    }
+
+    // Number of moves that can be taken back.
+    pub fn get_undo_count(&self) -> usize {
+        self.move_stack.len()
+    }
+
+    // Makes a move that can be taken back with pop_move.
+    pub fn push_move(&mut self, move_from: &Coordinates, move_to: &Coordinates, promotion_piece: Option<String>) -> bool {
+        self.make_move(move_from, move_to, true, promotion_piece)
+    }
+
+    // Takes back the last move, restoring the position, castling rights, en passant
+    // square, move clocks and move history as they were before it. Returns the move,
+    // so that it can be pushed again.
+    pub fn pop_move(&mut self) -> Option<(Coordinates, Coordinates, Option<String>)> {
+        let undo = self.move_stack.pop()?;
+        let popped_move = (undo.move_from.clone(), undo.move_to.clone(), undo.promotion_piece.clone());
+
+        self.pieces.insert(undo.move_to.clone(), None);
+        self.pieces.insert(undo.move_from.clone(), Some(undo.moved_piece));
+        if let Some((rook_from, rook_to)) = undo.rook_move {
+            if let Some(Some(mut rook)) = self.pieces.insert(rook_to, None) {
+                rook.set_coordinates(&rook_from);
+                self.pieces.insert(rook_from, Some(rook));
+            }
+        }
+        if let Some((coordinates, piece)) = undo.captured_piece {
+            self.pieces.insert(coordinates, Some(piece));
+        }
+
+        self.active_color = undo.active_color;
+        self.castle_options = undo.castle_options;
+        self.en_passant_square = undo.en_passant_square;
+        self.half_move_clock = undo.half_move_clock;
+        self.full_move_number = undo.full_move_number;
+        self.w_king_square = undo.w_king_square;
+        self.b_king_square = undo.b_king_square;
+        self.moves_count = undo.moves_count;
+        self.moves_history.truncate(undo.moves_history_length);
+        self.last_move_san = undo.last_move_san;
+        self.position_history.truncate(undo.position_history_length);
+
+        let color = self.active_color.clone();
+        self.generate_possible_moves();
+        self.update_check_status(&color);
+        self.update_check_status(&color.next());
+        self.fen = self.board_to_fen();
+        Some(popped_move)
     }
 
     pub fn update_castle_options_after_rook_move(
@@ -688,31 +861,28 @@ impl Board {
     }
 
     pub fn update_check_status(&mut self, color: &ActiveColor) {
-        let mut board_clone = self.clone();
-
-        let (coordinates_to_check, mut king_in_check) = match color {
-            ActiveColor::White => (&board_clone.w_king_square, &mut self.w_king_in_check),
-            ActiveColor::Black => (&board_clone.b_king_square, &mut self.b_king_in_check),
-        };
+        let in_check = self.to_position().in_check(color_index(color));
+        match color {
+            ActiveColor::White => self.w_king_in_check = in_check,
+            ActiveColor::Black => self.b_king_in_check = in_check,
+        }
+    }
 
-        for piece in self.pieces.values_mut() {
-            match piece {
-                Some(ref mut piece) => {
-                    if piece.get_color() != color.to_char() {
-                        if let Some(coordinates_to_check) = coordinates_to_check {
-                            println!("piece: {}, piece possible moves: {:?}", piece.get_symbol(), piece.get_possible_moves());
-                            if piece.get_possible_moves().contains(&(*coordinates_to_check).to_string()) {
-                                *king_in_check = true;
-                                return
-                            }
-                        }
-                    }
-                },
-                _ => {},
+    // Builds the compact bitboard representation used for move generation.
+    pub fn to_position(&self) -> Position {
+        let mut position = Position::new(
+            color_index(&self.active_color),
+            &self.castle_options,
+            &self.en_passant_square,
+        );
+        for (coordinates, piece) in self.pieces.iter() {
+            if let (Some(piece), Some(square)) = (piece, square_from_coordinates(coordinates)) {
+                if let Some((kind, color)) = piece.get_symbol().chars().nth(0).and_then(PieceKind::new_from_char) {
+                    position.put_piece(square, kind, color);
+                }
             }
         }
-
-        *king_in_check = false;
+        position
     }
 
     pub fn board_to_string(&self) -> String {
@@ -809,46 +979,36 @@ impl Board {
         ))
     }
 
-    fn generate_possible_moves(&mut self, color: &ActiveColor, calculate_check_moves: &bool) {
-        // let stockfish_board = StockfishBoard::from_fen(self.get_fen().as_str()).unwrap();
-        // let moves = stockfish_board.generate_moves();
-        // let mut move_from: String;
-        // let mut move_to: String;
-        //
-        // for piece_move in moves {
-        //     let piece_move = &piece_move.to_string();
-        //     (move_from, move_to) = (piece_move[0..2].to_string(), piece_move[2..4].to_string());
-        //     println!("move_from: {}, move_to: {}", move_from, move_to);
-        //     // self.add_move_to_possible_moves(&move_from, &move_to);
-        //
-        //     // let mut piece = self.pieces.get_mut(&Coordinates::new_from_string(move_from).unwrap()).unwrap();
-        //     // match piece {
-        //     //     Some(piece) => {
-        //     //         piece.get_possible_moves().push(move_to.clone());
-        //     //         self.pieces
-        //     //     },
-        //     // }
-        //     // println!("move: {}", pos_move.to_string());
-        // }
-
-        // println!("moves: {:?}", moves);
-        let mut validate_moves: Vec<String> = Vec::new();
-        let board_clone = &self.clone();
-        for piece in self.pieces.values_mut() {
+    // Recalculates the legal moves of the active color and stores them on its pieces.
+    // Pieces of the other color get an empty move list.
+    fn generate_possible_moves(&mut self) {
+        let position = self.to_position();
+        let mut moves_by_square: HashMap<Coordinates, Vec<String>> = HashMap::new();
+        for piece_move in position.legal_moves() {
+            let moves = moves_by_square.entry(piece_move.get_from_coordinates()).or_default();
+            let move_to = piece_move.get_to_coordinates().to_string();
+            // promotions produce one move per promotion piece for the same square
+            if !moves.contains(&move_to) {
+                moves.push(move_to);
+            }
+        }
+
+        let active_color = self.active_color.to_char();
+        self.possible_moves.clear();
+        for (coordinates, piece) in self.pieces.iter_mut() {
             if let Some(piece) = piece {
-                if piece.get_color() == color.to_char() {
-                    let possible_moves = piece.generate_piece_moves(board_clone, color, calculate_check_moves);
-                    for m in &possible_moves {
-                        validate_moves.push(format!("{}{}", piece.get_coordinates_string(), m));
-                    }
+                if piece.get_color() == active_color {
+                    let possible_moves = moves_by_square.remove(coordinates).unwrap_or_default();
                     self.possible_moves.insert(
-                        piece.get_coordinates_string(),
-                        (piece.get_symbol(), possible_moves)
+                        coordinates.to_string(),
+                        (piece.get_symbol(), possible_moves.clone())
                     );
+                    piece.set_possible_moves(possible_moves);
+                } else {
+                    piece.set_possible_moves(Vec::new());
                 }
             }
         }
-        println!("board.possible_moves: {:?}", self.possible_moves)
     }
 
     pub fn square_is_valid(&self, coordinates: &Coordinates) -> bool {
@@ -897,103 +1057,39 @@ impl Board {
         }
     }
 
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.to_position().perft(depth)
+    }
+
+    pub fn divide(&self, depth: u32) -> Vec<(String, u64)> {
+        self.to_position().divide(depth)
+    }
+
     pub fn king_in_check_after_move(
         &self,
         from_coordinates: &Coordinates,
         to_coordinates: &Coordinates,
         king_color: &ActiveColor,
     ) -> bool {
-            let mut board_after_move = self.clone();
-            board_after_move.make_move_without_move_validation(from_coordinates, to_coordinates, true);
-            board_after_move.king_is_in_check(&king_color)
+        let mut position = self.to_position();
+        match position.move_from_squares(from_coordinates, to_coordinates, None) {
+            Some(piece_move) => {
+                position.make_move(&piece_move);
+                position.in_check(color_index(king_color))
+            },
+            None => false,
+        }
     }
 
     pub fn king_is_in_check(&self, color: &ActiveColor) -> bool {
-        let king_square = match color {
-            ActiveColor::White => &self.w_king_square.to_owned().unwrap(),
-            ActiveColor::Black => &self.b_king_square.to_owned().unwrap(),
-        };
-
-        self.square_is_attacked(king_square, color)
+        self.to_position().in_check(color_index(color))
     }
 
     pub fn square_is_attacked(&self, coordinates: &Coordinates, color: &ActiveColor) -> bool {
-        let color_char = color.to_char();
-        for piece in self.pieces.values() {
-            match piece {
-                Some(piece) => {
-                    if piece.get_color() != color_char {
-                        let mut new_piece = piece.clone();
-                        // println!("Check validation: {}, {}", new_piece.get_symbol(), new_piece.get_color());
-                        new_piece.generate_piece_moves(&self, color, &false);
-                        // println!("Possible moves: {:?}", new_piece.get_possible_moves());
-                        if new_piece.get_possible_moves().contains(&coordinates.to_string()) {
-                            return true;
-                        }
-                    }
-                },
-                _ => {},
-            }
+        match square_from_coordinates(coordinates) {
+            Some(square) => self.to_position().is_square_attacked(square, 1 - color_index(color)),
+            None => false,
         }
-        false
-    }
-
-    pub fn square_is_attacked_new_board(&self, coordinates: &Coordinates, color: &ActiveColor) -> bool {
-        let mut new_board = self.clone();
-        new_board.active_color = new_board.active_color.next();
-        let color_char = color.to_char();
-        for piece in new_board.pieces.values() {
-            match piece {
-                Some(piece) => {
-                    if piece.get_color() != color_char {
-                        let mut new_piece = piece.clone();
-                        // println!("Check validation: {}, {}", new_piece.get_symbol(), new_piece.get_color());
-                        new_piece.generate_piece_moves(&new_board, color, &false);
-                        // println!("Possible moves: {:?}", new_piece.get_possible_moves());
-                        if new_piece.get_possible_moves().contains(&coordinates.to_string()) {
-                            return true;
-                        }
-                    }
-                },
-                _ => {},
-            }
-        }
-        false
-    }
-
-    pub fn square_is_visible(&self, coordinates: &Coordinates, color: &ActiveColor) -> bool {
-        let color_char = color.to_char();
-        let mut piece_enum: PieceEnum;
-        for piece in self.pieces.values() {
-            match piece {
-                Some(piece) => {
-                    piece_enum = piece.clone();
-                    piece_enum.generate_piece_moves(&self, &color.next(), &false);
-                    if piece_enum.get_color() != color_char && piece_enum.get_possible_moves().contains(&coordinates.to_string()) {
-                        return true;
-                    }
-                },
-                _ => {},
-            }
-        }
-        false
-    }
-
-    pub fn kings_adjacent(&self, square_to_check: &Coordinates) -> bool {
-        let another_king_square = match self.active_color {
-            ActiveColor::White => &self.b_king_square,
-            ActiveColor::Black => &self.w_king_square,
-        };
-
-        let distance_between_squares = (
-            (square_to_check.column - another_king_square.to_owned().unwrap().column).abs(),
-            (square_to_check.row - another_king_square.to_owned().unwrap().row).abs(),
-        );
-
-        if distance_between_squares.0 <= 1 && distance_between_squares.1 <= 1 {
-            return true;
-        }
-        false
     }
 
     pub fn distance_between_coordinates(&self, square1: &Coordinates, square2: &Coordinates) -> (u32, u32) {
@@ -1003,65 +1099,9 @@ impl Board {
         )
     }
 
-    pub fn generate_castle_moves(&mut self, active_color: &ActiveColor) {
-        match active_color {
-            ActiveColor::White => {
-                if self.w_king_in_check {
-                    return;
-                }
-            },
-            ActiveColor::Black => {
-                if self.b_king_in_check {
-                    return;
-                }
-            }
-        }
-
-        let (king_square, castle_fen) = match active_color {
-            ActiveColor::White => (&self.w_king_square.to_owned().unwrap(), ['K', 'Q']),
-            ActiveColor::Black => (&self.b_king_square.to_owned().unwrap(), ['k', 'q'])
-        };
-
-        for (index, castle_move) in [1i8, -1i8].iter().enumerate() {
-            let square1 = &Coordinates::new_from_int(
-                &(king_square.column + castle_move),
-                &king_square.row,
-            );
-            let square2 = &Coordinates::new_from_int(
-                &(king_square.column + castle_move * 2),
-                &king_square.row,
-            );
-
-            if self.castle_options.contains(castle_fen[index]) &&
-                self.square_is_free(square1) &&
-                !self.square_is_visible(square1, active_color) &&
-                self.square_is_free(square2) &&
-                !self.square_is_visible(square2, active_color) {
-
-                let king_piece = self.pieces.get_mut(king_square).unwrap();
-                match king_piece {
-                    Some(king_piece) => {
-                        let mut moves = king_piece.get_possible_moves();
-                        moves.push(Coordinates::new_from_int(
-                            &(king_square.column + castle_move * 2),
-                            &king_square.row,
-                        ).to_string());
-                        king_piece.set_possible_moves(moves);
-                        // castle_squares[index] = Some(Coordinates::new_from_int(
-                        //     &(king_square.column + castle_move * 2),
-                        //     &king_square.row,
-                        // ));
-                    },
-                    _ => {},
-                }
-            }
-        }
-    }
-
     pub fn get_game_status_and_end_condition(&self) -> (GameStatus, GameEndCondition) {
         // todo: add time out game end condition
-        // todo: add draw by repetition, players' agreement,
-        // todo: 50 moves without captures, insufficient material
+        // todo: add players' agreement,
 
         let mut player_has_moves = false;
         for piece in self.pieces.values() {
@@ -1092,33 +1132,37 @@ impl Board {
             }
         }
 
+        if self.to_position().is_insufficient_material() {
+            return (GameStatus::Finished, GameEndCondition::InsufficientMaterial);
+        }
+
+        // threefold repetition only allows a claim, fivefold ends the game
+        if self.get_repetition_count() >= 5 {
+            return (GameStatus::Finished, GameEndCondition::FivefoldRepetition);
+        }
+
+        // likewise fifty moves allow a claim, seventy-five moves end the game
+        if self.half_move_clock >= 150 {
+            return (GameStatus::Finished, GameEndCondition::SeventyFiveMoveRule);
+        }
+
         (GameStatus::Ongoing, GameEndCondition::None)
     }
 
-    fn add_move_to_made_moves(
-        &mut self,
-        move_from: &Coordinates,
-        move_to: &Coordinates,
-    ) {
+    fn add_move_to_made_moves(&mut self, san: &str) {
         match self.active_color {
             ActiveColor::White => {
                 self.moves_count = self.moves_count + 1;
-                self.moves_history.push_str((
-                    self.moves_count.to_string() + " " +
-                        move_from.to_string().as_str() +
-                        move_to.to_string().as_str() + " "
-                ).as_str()
-                );
+                self.moves_history.push_str(format!("{}. {} ", self.full_move_number, san).as_str());
+            },
+            // a game set up with Black to move starts with e.g. "1... "
+            ActiveColor::Black if self.moves_history.is_empty() => {
+                self.moves_history.push_str(format!("{}... {} ", self.full_move_number, san).as_str());
             },
             ActiveColor::Black => {
-                self.moves_history.push_str((
-                    move_from.to_string() +
-                        move_to.to_string().as_str() + "  "
-                ).as_str()
-                );
+                self.moves_history.push_str(format!("{} ", san).as_str());
             },
         }
-
+        self.last_move_san = Some(san.to_string());
     }
 }
-
@@ -1,9 +1,29 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Color asked for when creating a game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum Color {
     White,
     Black,
     Random,
 }
 
+impl Color {
+    // Random picks either side with equal chance.
+    pub fn resolve(&self) -> ActiveColor {
+        match self {
+            Color::White => ActiveColor::White,
+            Color::Black => ActiveColor::Black,
+            Color::Random => match Uuid::new_v4().as_bytes()[0] & 1 {
+                0 => ActiveColor::White,
+                _ => ActiveColor::Black,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ActiveColor {
     White,
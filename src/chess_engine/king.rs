@@ -1,14 +1,6 @@
-use std::collections::HashSet;
-use crate::chess_engine::board::Board;
-use crate::chess_engine::color::ActiveColor;
 use crate::chess_engine::coordinates::Coordinates;
 use crate::chess_engine::piece::Piece;
 
-const KING_DIRECTIONS: [(i8, i8); 8] = [
-    (0, 1), (1, 0), (0, -1), (-1, 0),
-    (1, 1), (-1, -1), (-1, 1), (1, -1)
-];
-
 #[derive(Debug, Clone)]
 pub struct King {
     coordinates: Coordinates,
@@ -55,46 +47,6 @@ impl Piece for King {
         self.possible_moves = moves
     }
 
-    fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String> {
-        // if self.color != board.get_active_color().to_char() {
-        //     self.possible_moves = Vec::new();
-        //     return self.get_possible_moves();
-        // }
-
-        let mut possible_moves = HashSet::new();
-        let mut next_square: Coordinates;
-
-        for direction in KING_DIRECTIONS {
-            next_square = Coordinates::new_from_int(
-                &(self.coordinates.column + direction.0),
-                &(self.coordinates.row + direction.1)
-            );
-
-            // king_in_check_after_move = *calculate_check_moves && board.king_in_check_after_move(&self.coordinates, &next_square);
-            if board.square_is_valid(&next_square) &&
-                !board.square_contains_piece_of_same_color(&next_square, &self.color) &&
-                !(*calculate_check_moves && board.king_in_check_after_move(
-                    &self.coordinates,
-                    &next_square,
-                    &ActiveColor::new_from_char(self.color).unwrap(),
-                )) &&
-                // !board.square_is_attacked_new_board(&next_square, &board.get_active_color()) &&
-                !board.kings_adjacent(&next_square) {
-                possible_moves.insert(next_square.to_string());
-            }
-        }
-
-        //castle squares
-        // for square in board.generate_castle_moves(&board.get_active_color()) {
-        //     if let Some(square) = square {
-        //         possible_moves.insert(square.to_string());
-        //     }
-        // }
-
-        self.possible_moves = possible_moves.into_iter().collect();
-        self.possible_moves.clone()
-    }
-
     fn get_symbol(&self) -> String {
         self.symbol.clone()
     }
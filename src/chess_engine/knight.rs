@@ -1,14 +1,6 @@
-use std::collections::HashSet;
-use crate::chess_engine::board::Board;
-use crate::chess_engine::color::ActiveColor;
 use crate::chess_engine::coordinates::Coordinates;
 use crate::chess_engine::piece::Piece;
 
-const KNIGHT_DIRECTIONS: [(i8, i8); 8] = [
-    (1, 2), (2, 1), (2, -1), (1, -2),
-    (-1, -2), (-2, -1), (-2, 1), (-1, 2),
-];
-
 #[derive(Debug, Clone)]
 pub struct Knight {
     coordinates: Coordinates,
@@ -50,36 +42,6 @@ impl Piece for Knight {
         self.possible_moves = moves
     }
 
-    fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String> {
-        // if self.color != board.get_active_color().to_char() {
-        //     self.possible_moves = Vec::new();
-        //     return self.get_possible_moves();
-        // }
-
-        let mut possible_moves = HashSet::new();
-        let mut next_square: Coordinates;
-
-        for direction in KNIGHT_DIRECTIONS {
-            next_square = Coordinates::new_from_int(
-                &(self.coordinates.column + direction.0),
-                &(self.coordinates.row + direction.1)
-            );
-
-            if board.square_is_valid(&next_square) &&
-                !board.square_contains_piece_of_same_color(&next_square, &self.color)
-                && !(*calculate_check_moves && board.king_in_check_after_move(
-                &self.coordinates,
-                &next_square,
-                &ActiveColor::new_from_char(self.color).unwrap(),
-            )) {
-                possible_moves.insert(next_square.to_string());
-            }
-        }
-
-        self.possible_moves = possible_moves.into_iter().collect();
-        self.possible_moves.clone()
-    }
-
     fn get_symbol(&self) -> String {
         self.symbol.clone()
     }
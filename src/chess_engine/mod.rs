@@ -4,8 +4,13 @@ pub mod king;
 pub mod knight;
 pub mod pawn;
 pub mod piece;
+pub mod position;
 pub mod queen;
 pub mod rook;
+pub mod san;
 pub mod square;
 pub mod coordinates;
-pub mod color;
\ No newline at end of file
+pub mod color;
+
+#[cfg(test)]
+mod tests;
\ No newline at end of file
@@ -1,6 +1,3 @@
-use std::collections::HashSet;
-use crate::chess_engine::board::Board;
-use crate::chess_engine::color::ActiveColor;
 use crate::chess_engine::coordinates::Coordinates;
 use crate::chess_engine::piece::Piece;
 
@@ -45,75 +42,6 @@ impl Piece for Pawn {
         self.possible_moves = moves
     }
 
-    fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String> {
-        // if self.color != board.get_active_color().to_char() {
-        //     self.possible_moves = Vec::new();
-        //     return Vec::new();
-        // }
-
-        let mut possible_moves = HashSet::new();
-        let rows = board.get_rows();
-        let direction: i8 = if self.color == 'w' { 1 } else { -1 };
-        let mut next_square: Coordinates;
-
-        // one square move
-        next_square = Coordinates::new_from_int(
-            &self.coordinates.column, &(self.coordinates.row + direction)
-        );
-
-        if board.square_is_valid(&next_square)
-            && board.square_is_free(&next_square)
-            && !(*calculate_check_moves && board.king_in_check_after_move(
-            &self.coordinates,
-            &next_square,
-            &ActiveColor::new_from_char(self.color).unwrap(),
-        )) {
-            possible_moves.insert(next_square.to_string());
-
-
-            // two squares move from starting position
-            if (self.color == 'w' && self.coordinates.row_char() == rows.chars().nth(1).unwrap()) ||
-                (self.color == 'b' && self.coordinates.row_char() == rows.chars().rev().nth(1).unwrap()) {
-                next_square = Coordinates::new_from_int(
-                    &self.coordinates.column, &(self.coordinates.row + 2 * direction)
-                );
-                if board.square_is_valid(&next_square)
-                    && board.square_is_free(&next_square)
-                    && !(*calculate_check_moves && board.king_in_check_after_move(
-                    &self.coordinates,
-                    &next_square,
-                    &ActiveColor::new_from_char(self.color).unwrap(),
-                )) {
-                    possible_moves.insert(next_square.to_string());
-                }
-            }
-        }
-        // capture move
-        for col_shift in [-1, 1].iter() {
-            next_square = Coordinates::new_from_int(
-                &(self.coordinates.column + col_shift),
-                &(self.coordinates.row + direction)
-            );
-
-            if board.square_is_valid(&next_square) && (
-                board.square_is_capturable(&next_square, &self.color) || (
-                    board.get_active_color_string() == self.color.to_string() &&
-                    board.get_en_passant_square() == next_square.to_string()
-                )
-            )
-                && !(*calculate_check_moves && board.king_in_check_after_move(
-                &self.coordinates,
-                &next_square,
-                &ActiveColor::new_from_char(self.color).unwrap(),
-            )) {
-                possible_moves.insert(next_square.to_string());
-            }
-        }
-
-        self.possible_moves = possible_moves.into_iter().collect();
-        self.possible_moves.clone()
-    }
-
     fn get_symbol(&self) -> String {
         self.symbol.clone()
     }
@@ -6,8 +6,6 @@ use crate::chess_engine::{
     queen::Queen,
     king::King,
 };
-use crate::chess_engine::board::Board;
-use crate::chess_engine::color::ActiveColor;
 use crate::chess_engine::coordinates::Coordinates;
 
 #[derive(Debug, Clone)]
@@ -29,7 +27,6 @@ enum FunctionVariant<F, Fp, P> {
 pub trait Piece {
     fn get_possible_moves(&self) -> Vec<String>;
     fn set_possible_moves(&mut self, moves: Vec<String>);
-    fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String>;
     fn get_symbol(&self) -> String;
     fn get_color(&self) -> char;
     fn get_coordinates(&self) -> Coordinates;
@@ -140,18 +137,6 @@ impl PieceEnum {
             piece.set_possible_moves(params), moves),)
     }
 
-    pub fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String> {
-        let mut possible_moves: Vec<String> = Vec::new();
-        dispatch_variant_mut(
-            self,
-            FunctionVariant::<fn(&mut dyn Piece), _, (&Board, &ActiveColor, &bool)>::WithParams(
-                |piece: &mut dyn Piece, (board_, color, calculate_check)|
-                    possible_moves = piece.generate_piece_moves(board_, color, calculate_check), (board, color, calculate_check_moves)
-            ),
-        );
-        possible_moves
-    }
-
     pub fn get_symbol(&self) -> String {
         let mut symbol: Option<String> = None;
         dispatch_variant(self,
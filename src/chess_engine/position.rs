@@ -0,0 +1,565 @@
+// Compact bitboard + mailbox representation of a chess position.
+//
+// `Board` keeps pieces in a HashMap of `PieceEnum`, which is convenient for the
+// API and the database layer, but far too expensive to clone for every candidate
+// move. `Position` is a small `Copy` struct that is built once per move generation
+// pass; legality of a move is checked by copying the position, applying the move
+// and testing whether the mover's king is attacked. No heap allocation happens
+// except for the returned move list.
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use crate::chess_engine::color::ActiveColor;
+use crate::chess_engine::coordinates::Coordinates;
+
+pub type Bitboard = u64;
+
+pub const WHITE: usize = 0;
+pub const BLACK: usize = 1;
+
+pub const CASTLE_WHITE_KING_SIDE: u8 = 1;
+pub const CASTLE_WHITE_QUEEN_SIDE: u8 = 2;
+pub const CASTLE_BLACK_KING_SIDE: u8 = 4;
+pub const CASTLE_BLACK_QUEEN_SIDE: u8 = 8;
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2), (2, 1), (2, -1), (1, -2),
+    (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+];
+
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1), (1, 0), (0, -1), (-1, 0),
+    (1, 1), (-1, -1), (-1, 1), (1, -1),
+];
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, -1), (-1, 1), (1, -1)];
+
+// a1, c1, ..., b2, d2, ...
+const DARK_SQUARES: Bitboard = 0xAA55_AA55_AA55_AA55;
+
+const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(KNIGHT_OFFSETS);
+const KING_ATTACKS: [Bitboard; 64] = leaper_attacks(KING_OFFSETS);
+
+const PROMOTION_KINDS: [PieceKind; 4] = [
+    PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight,
+];
+
+const fn leaper_attacks(offsets: [(i8, i8); 8]) -> [Bitboard; 64] {
+    let mut table = [0u64; 64];
+    let mut square = 0;
+    while square < 64 {
+        let column = (square % 8) as i8;
+        let row = (square / 8) as i8;
+        let mut i = 0;
+        while i < 8 {
+            let (d_column, d_row) = offsets[i];
+            let (next_column, next_row) = (column + d_column, row + d_row);
+            if next_column >= 0 && next_column < 8 && next_row >= 0 && next_row < 8 {
+                table[square] |= 1u64 << (next_row * 8 + next_column);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+fn slider_attacks(square: u8, occupied: Bitboard, directions: &[(i8, i8); 4]) -> Bitboard {
+    let mut attacks = 0;
+    let (column, row) = ((square % 8) as i8, (square / 8) as i8);
+    for (d_column, d_row) in directions {
+        let (mut next_column, mut next_row) = (column + d_column, row + d_row);
+        while (0..8).contains(&next_column) && (0..8).contains(&next_row) {
+            let bit = 1u64 << (next_row * 8 + next_column);
+            attacks |= bit;
+            if occupied & bit != 0 {
+                break;
+            }
+            next_column += d_column;
+            next_row += d_row;
+        }
+    }
+    attacks
+}
+
+fn pawn_attacks(square: u8, color: usize) -> Bitboard {
+    let (column, row) = ((square % 8) as i8, (square / 8) as i8);
+    let next_row = if color == WHITE { row + 1 } else { row - 1 };
+    let mut attacks = 0;
+    if !(0..8).contains(&next_row) {
+        return attacks;
+    }
+    for next_column in [column - 1, column + 1] {
+        if (0..8).contains(&next_column) {
+            attacks |= 1u64 << (next_row * 8 + next_column);
+        }
+    }
+    attacks
+}
+
+pub fn color_index(color: &ActiveColor) -> usize {
+    match color {
+        ActiveColor::White => WHITE,
+        ActiveColor::Black => BLACK,
+    }
+}
+
+pub fn square_from_coordinates(coordinates: &Coordinates) -> Option<u8> {
+    if (0..8).contains(&coordinates.column) && (0..8).contains(&coordinates.row) {
+        Some((coordinates.row * 8 + coordinates.column) as u8)
+    } else {
+        None
+    }
+}
+
+pub fn coordinates_from_square(square: u8) -> Coordinates {
+    Coordinates::new_from_int(&((square % 8) as i8), &((square / 8) as i8))
+}
+
+pub fn castling_rights_from_string(castle_options: &str) -> u8 {
+    castle_options.chars().fold(0, |rights, c| match c {
+        'K' => rights | CASTLE_WHITE_KING_SIDE,
+        'Q' => rights | CASTLE_WHITE_QUEEN_SIDE,
+        'k' => rights | CASTLE_BLACK_KING_SIDE,
+        'q' => rights | CASTLE_BLACK_QUEEN_SIDE,
+        _ => rights,
+    })
+}
+
+// Castling rights lost when a piece leaves or arrives at the given square.
+fn castling_rights_mask(square: u8) -> u8 {
+    match square {
+        0 => CASTLE_WHITE_QUEEN_SIDE,
+        4 => CASTLE_WHITE_KING_SIDE | CASTLE_WHITE_QUEEN_SIDE,
+        7 => CASTLE_WHITE_KING_SIDE,
+        56 => CASTLE_BLACK_QUEEN_SIDE,
+        60 => CASTLE_BLACK_KING_SIDE | CASTLE_BLACK_QUEEN_SIDE,
+        63 => CASTLE_BLACK_KING_SIDE,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    pub fn index(&self) -> usize {
+        match self {
+            PieceKind::Pawn => 0,
+            PieceKind::Knight => 1,
+            PieceKind::Bishop => 2,
+            PieceKind::Rook => 3,
+            PieceKind::Queen => 4,
+            PieceKind::King => 5,
+        }
+    }
+
+    pub fn new_from_char(symbol: char) -> Option<(PieceKind, usize)> {
+        let color = if symbol.is_uppercase() { WHITE } else { BLACK };
+        let kind = match symbol.to_ascii_lowercase() {
+            'p' => PieceKind::Pawn,
+            'n' => PieceKind::Knight,
+            'b' => PieceKind::Bishop,
+            'r' => PieceKind::Rook,
+            'q' => PieceKind::Queen,
+            'k' => PieceKind::King,
+            _ => return None,
+        };
+        Some((kind, color))
+    }
+
+    pub fn to_char(self, color: usize) -> char {
+        let symbol = match self {
+            PieceKind::Pawn => 'p',
+            PieceKind::Knight => 'n',
+            PieceKind::Bishop => 'b',
+            PieceKind::Rook => 'r',
+            PieceKind::Queen => 'q',
+            PieceKind::King => 'k',
+        };
+        match color {
+            WHITE => symbol.to_ascii_uppercase(),
+            _ => symbol,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    Quiet,
+    Capture,
+    DoublePawnPush,
+    EnPassant,
+    Castle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub kind: MoveKind,
+    pub promotion: Option<PieceKind>,
+}
+
+impl Move {
+    pub fn get_from_coordinates(&self) -> Coordinates {
+        coordinates_from_square(self.from)
+    }
+
+    pub fn get_to_coordinates(&self) -> Coordinates {
+        coordinates_from_square(self.to)
+    }
+
+}
+
+// Long algebraic (UCI) notation, e.g. "e2e4" or "e7e8q".
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.get_from_coordinates().to_string(), self.get_to_coordinates().to_string())?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion.to_char(BLACK))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    mailbox: [Option<(PieceKind, usize)>; 64],
+    colors: [Bitboard; 2],
+    kinds: [Bitboard; 6],
+    side_to_move: usize,
+    castling_rights: u8,
+    en_passant_square: Option<u8>,
+}
+
+impl Position {
+    pub fn new(side_to_move: usize, castle_options: &str, en_passant_square: &str) -> Position {
+        Position {
+            mailbox: [None; 64],
+            colors: [0; 2],
+            kinds: [0; 6],
+            side_to_move,
+            castling_rights: castling_rights_from_string(castle_options),
+            en_passant_square: Coordinates::new_from_string(&en_passant_square.to_string())
+                .and_then(|coordinates| square_from_coordinates(&coordinates)),
+        }
+    }
+
+    pub fn put_piece(&mut self, square: u8, kind: PieceKind, color: usize) {
+        self.remove_piece(square);
+        let bit = 1u64 << square;
+        self.mailbox[square as usize] = Some((kind, color));
+        self.colors[color] |= bit;
+        self.kinds[kind.index()] |= bit;
+    }
+
+    pub fn remove_piece(&mut self, square: u8) -> Option<(PieceKind, usize)> {
+        let piece = self.mailbox[square as usize].take();
+        if let Some((kind, color)) = piece {
+            let bit = 1u64 << square;
+            self.colors[color] &= !bit;
+            self.kinds[kind.index()] &= !bit;
+        }
+        piece
+    }
+
+    pub fn get_piece(&self, square: u8) -> Option<(PieceKind, usize)> {
+        self.mailbox[square as usize]
+    }
+
+    pub fn get_side_to_move(&self) -> usize {
+        self.side_to_move
+    }
+
+    pub fn pieces(&self, kind: PieceKind, color: usize) -> Bitboard {
+        self.kinds[kind.index()] & self.colors[color]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[WHITE] | self.colors[BLACK]
+    }
+
+    pub fn king_square(&self, color: usize) -> Option<u8> {
+        let kings = self.pieces(PieceKind::King, color);
+        match kings {
+            0 => None,
+            _ => Some(kings.trailing_zeros() as u8),
+        }
+    }
+
+    pub fn is_square_attacked(&self, square: u8, by_color: usize) -> bool {
+        let occupied = self.occupied();
+        let queens = self.pieces(PieceKind::Queen, by_color);
+
+        pawn_attacks(square, 1 - by_color) & self.pieces(PieceKind::Pawn, by_color) != 0
+            || KNIGHT_ATTACKS[square as usize] & self.pieces(PieceKind::Knight, by_color) != 0
+            || KING_ATTACKS[square as usize] & self.pieces(PieceKind::King, by_color) != 0
+            || slider_attacks(square, occupied, &BISHOP_DIRECTIONS)
+                & (self.pieces(PieceKind::Bishop, by_color) | queens) != 0
+            || slider_attacks(square, occupied, &ROOK_DIRECTIONS)
+                & (self.pieces(PieceKind::Rook, by_color) | queens) != 0
+    }
+
+    pub fn in_check(&self, color: usize) -> bool {
+        match self.king_square(color) {
+            Some(square) => self.is_square_attacked(square, 1 - color),
+            None => false,
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.generate_moves(self.side_to_move, true)
+    }
+
+    // Generates moves for `color`. En passant and castling are only available to
+    // the side to move. With `legal` set, moves that leave the king in check are
+    // filtered out; otherwise the pseudo-legal set is returned.
+    pub fn generate_moves(&self, color: usize, legal: bool) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::with_capacity(64);
+        let own = self.colors[color];
+        let enemy = self.colors[1 - color];
+        let occupied = own | enemy;
+
+        let mut pawns = self.pieces(PieceKind::Pawn, color);
+        while pawns != 0 {
+            let from = pawns.trailing_zeros() as u8;
+            pawns &= pawns - 1;
+            self.generate_pawn_moves(from, color, occupied, enemy, &mut moves);
+        }
+
+        for kind in [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen, PieceKind::King] {
+            let mut pieces = self.pieces(kind, color);
+            while pieces != 0 {
+                let from = pieces.trailing_zeros() as u8;
+                pieces &= pieces - 1;
+
+                let mut targets = match kind {
+                    PieceKind::Knight => KNIGHT_ATTACKS[from as usize],
+                    PieceKind::King => KING_ATTACKS[from as usize],
+                    PieceKind::Bishop => slider_attacks(from, occupied, &BISHOP_DIRECTIONS),
+                    PieceKind::Rook => slider_attacks(from, occupied, &ROOK_DIRECTIONS),
+                    _ => slider_attacks(from, occupied, &BISHOP_DIRECTIONS)
+                        | slider_attacks(from, occupied, &ROOK_DIRECTIONS),
+                } & !own;
+
+                while targets != 0 {
+                    let to = targets.trailing_zeros() as u8;
+                    targets &= targets - 1;
+                    let kind = if enemy & (1u64 << to) != 0 { MoveKind::Capture } else { MoveKind::Quiet };
+                    moves.push(Move { from, to, kind, promotion: None });
+                }
+            }
+        }
+
+        if legal && color == self.side_to_move {
+            self.generate_castle_moves(color, occupied, &mut moves);
+        }
+
+        if legal {
+            moves.retain(|m| self.is_legal(m, color));
+        }
+        moves
+    }
+
+    fn generate_pawn_moves(&self, from: u8, color: usize, occupied: Bitboard, enemy: Bitboard, moves: &mut Vec<Move>) {
+        let row = from / 8;
+        let (start_row, promotion_row) = if color == WHITE { (1, 7) } else { (6, 0) };
+        let forward = |square: u8| -> Option<u8> {
+            match color {
+                WHITE if square < 56 => Some(square + 8),
+                BLACK if square >= 8 => Some(square - 8),
+                _ => None,
+            }
+        };
+
+        let mut push = |to: u8, kind: MoveKind| {
+            if to / 8 == promotion_row {
+                for promotion in PROMOTION_KINDS {
+                    moves.push(Move { from, to, kind, promotion: Some(promotion) });
+                }
+            } else {
+                moves.push(Move { from, to, kind, promotion: None });
+            }
+        };
+
+        if let Some(one_step) = forward(from) {
+            if occupied & (1u64 << one_step) == 0 {
+                push(one_step, MoveKind::Quiet);
+                if row == start_row {
+                    if let Some(two_steps) = forward(one_step) {
+                        if occupied & (1u64 << two_steps) == 0 {
+                            push(two_steps, MoveKind::DoublePawnPush);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut captures = pawn_attacks(from, color);
+        while captures != 0 {
+            let to = captures.trailing_zeros() as u8;
+            captures &= captures - 1;
+            if enemy & (1u64 << to) != 0 {
+                push(to, MoveKind::Capture);
+            } else if color == self.side_to_move && self.en_passant_square == Some(to) {
+                push(to, MoveKind::EnPassant);
+            }
+        }
+    }
+
+    fn generate_castle_moves(&self, color: usize, occupied: Bitboard, moves: &mut Vec<Move>) {
+        let (king_from, king_side, queen_side) = match color {
+            WHITE => (4u8, CASTLE_WHITE_KING_SIDE, CASTLE_WHITE_QUEEN_SIDE),
+            _ => (60u8, CASTLE_BLACK_KING_SIDE, CASTLE_BLACK_QUEEN_SIDE),
+        };
+
+        if self.get_piece(king_from) != Some((PieceKind::King, color)) || self.in_check(color) {
+            return;
+        }
+
+        // (right, rook square, squares that must be empty, squares the king crosses)
+        let options: [(u8, u8, &[u8], [u8; 2]); 2] = [
+            (king_side, king_from + 3, &[king_from + 1, king_from + 2], [king_from + 1, king_from + 2]),
+            (queen_side, king_from - 4, &[king_from - 1, king_from - 2, king_from - 3], [king_from - 1, king_from - 2]),
+        ];
+
+        for (right, rook_square, empty_squares, king_path) in options {
+            if self.castling_rights & right == 0
+                || self.get_piece(rook_square) != Some((PieceKind::Rook, color))
+                || empty_squares.iter().any(|square| occupied & (1u64 << square) != 0)
+                || king_path.iter().any(|square| self.is_square_attacked(*square, 1 - color)) {
+                continue;
+            }
+            moves.push(Move { from: king_from, to: king_path[1], kind: MoveKind::Castle, promotion: None });
+        }
+    }
+
+    fn is_legal(&self, piece_move: &Move, color: usize) -> bool {
+        let mut position = *self;
+        position.make_move(piece_move);
+        !position.in_check(color)
+    }
+
+    // Neither side can checkmate: bare kings, a single minor piece, or only
+    // bishops that all stand on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_pieces_or_pawns = self.kinds[PieceKind::Pawn.index()]
+            | self.kinds[PieceKind::Rook.index()]
+            | self.kinds[PieceKind::Queen.index()];
+        if heavy_pieces_or_pawns != 0 {
+            return false;
+        }
+
+        let knights = self.kinds[PieceKind::Knight.index()];
+        let bishops = self.kinds[PieceKind::Bishop.index()];
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
+
+    // Identifies the position for repetition detection: piece placement, side to
+    // move, castling rights and the en passant square, the latter only when an
+    // en passant capture is actually possible.
+    pub fn repetition_key(&self) -> u64 {
+        let mut position = *self;
+        if self.en_passant_square.is_some()
+            && !self.legal_moves().iter().any(|piece_move| piece_move.kind == MoveKind::EnPassant) {
+            position.en_passant_square = None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        position.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Counts the leaf nodes of the legal move tree of the given depth.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        moves.iter().map(|piece_move| {
+            let mut position = *self;
+            position.make_move(piece_move);
+            position.perft(depth - 1)
+        }).sum()
+    }
+
+    // Perft split by root move, used to find the move where two generators disagree.
+    pub fn divide(&self, depth: u32) -> Vec<(String, u64)> {
+        let mut nodes: Vec<(String, u64)> = self.legal_moves().iter().map(|piece_move| {
+            let mut position = *self;
+            position.make_move(piece_move);
+            (piece_move.to_string(), position.perft(depth.saturating_sub(1)))
+        }).collect();
+        nodes.sort();
+        nodes
+    }
+
+    // Builds a move from a pair of squares, inferring castling, en passant and
+    // double pawn pushes from the piece standing on `from`.
+    pub fn move_from_squares(&self, from: &Coordinates, to: &Coordinates, promotion: Option<PieceKind>) -> Option<Move> {
+        let (from, to) = (square_from_coordinates(from)?, square_from_coordinates(to)?);
+        let (kind, _) = self.get_piece(from)?;
+
+        let move_kind = match kind {
+            PieceKind::King if (from as i8 - to as i8).abs() == 2 => MoveKind::Castle,
+            PieceKind::Pawn if self.en_passant_square == Some(to) && from % 8 != to % 8 => MoveKind::EnPassant,
+            PieceKind::Pawn if (from as i8 - to as i8).abs() == 16 => MoveKind::DoublePawnPush,
+            _ if self.get_piece(to).is_some() => MoveKind::Capture,
+            _ => MoveKind::Quiet,
+        };
+        Some(Move { from, to, kind: move_kind, promotion })
+    }
+
+    // Applies a move without any validation. The side to move becomes the
+    // opponent of the piece that moved.
+    pub fn make_move(&mut self, piece_move: &Move) {
+        let (kind, color) = match self.remove_piece(piece_move.from) {
+            Some(piece) => piece,
+            None => return,
+        };
+
+        match piece_move.kind {
+            MoveKind::EnPassant => {
+                let captured_square = if color == WHITE { piece_move.to - 8 } else { piece_move.to + 8 };
+                self.remove_piece(captured_square);
+            },
+            MoveKind::Castle => {
+                let (rook_from, rook_to) = if piece_move.to > piece_move.from {
+                    (piece_move.from + 3, piece_move.from + 1)
+                } else {
+                    (piece_move.from - 4, piece_move.from - 1)
+                };
+                if let Some((rook, rook_color)) = self.remove_piece(rook_from) {
+                    self.put_piece(rook_to, rook, rook_color);
+                }
+            },
+            _ => {},
+        }
+
+        self.put_piece(piece_move.to, piece_move.promotion.unwrap_or(kind), color);
+
+        self.castling_rights &= !(castling_rights_mask(piece_move.from) | castling_rights_mask(piece_move.to));
+        self.en_passant_square = match piece_move.kind {
+            MoveKind::DoublePawnPush => Some((piece_move.from + piece_move.to) / 2),
+            _ => None,
+        };
+        self.side_to_move = 1 - color;
+    }
+}
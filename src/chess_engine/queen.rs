@@ -1,14 +1,6 @@
-use std::collections::HashSet;
-use crate::chess_engine::board::Board;
-use crate::chess_engine::color::ActiveColor;
 use crate::chess_engine::coordinates::Coordinates;
 use crate::chess_engine::piece::Piece;
 
-const QUEEN_DIRECTIONS: [(i8, i8); 8] = [
-    (0, 1), (1, 0), (0, -1), (-1, 0),
-    (1, 1), (-1, -1), (-1, 1), (1, -1)
-];
-
 #[derive(Debug, Clone)]
 pub struct Queen {
     coordinates: Coordinates,
@@ -50,48 +42,6 @@ impl Piece for Queen {
         self.possible_moves = moves
     }
 
-    fn generate_piece_moves(&mut self, board: &Board, color: &ActiveColor, calculate_check_moves: &bool) -> Vec<String> {
-        // if self.color != board.get_active_color().to_char() {
-        //     self.possible_moves = Vec::new();
-        //     return self.get_possible_moves();
-        // }
-
-        let mut possible_moves = HashSet::new();
-        let mut next_square: Coordinates;
-
-        for direction in QUEEN_DIRECTIONS {
-            next_square = self.coordinates.clone();
-            loop {
-                next_square = Coordinates::new_from_int(
-                    &(next_square.column + direction.0),
-                    &(next_square.row + direction.1)
-                );
-
-                if !board.square_is_valid(&next_square) ||
-                    board.square_contains_piece_of_same_color(&next_square, &self.color) {
-                    break
-                }
-
-                if board.square_is_valid(&next_square)
-                    && !(*calculate_check_moves && board.king_in_check_after_move(
-                    &self.coordinates,
-                    &next_square,
-                    &ActiveColor::new_from_char(self.color).unwrap(),
-                )) {
-                    if board.square_is_free(&next_square) {
-                        possible_moves.insert(next_square.to_string());
-                    } else if board.square_is_capturable(&next_square, &self.get_color()) {
-                        possible_moves.insert(next_square.to_string());
-                        break
-                    }
-                }
-            }
-        }
-
-        self.possible_moves = possible_moves.into_iter().collect();
-        self.possible_moves.clone()
-    }
-
     fn get_symbol(&self) -> String {
         self.symbol.clone()
     }
@@ -0,0 +1,151 @@
+// Standard Algebraic Notation (SAN) encoding and decoding of moves.
+use crate::chess_engine::coordinates::Coordinates;
+use crate::chess_engine::position::{Move, MoveKind, PieceKind, Position, WHITE};
+
+pub fn move_to_san(position: &Position, piece_move: &Move) -> String {
+    let (kind, color) = match position.get_piece(piece_move.from) {
+        Some(piece) => piece,
+        None => return piece_move.to_string(),
+    };
+
+    let mut san = match piece_move.kind {
+        MoveKind::Castle if piece_move.to > piece_move.from => "O-O".to_string(),
+        MoveKind::Castle => "O-O-O".to_string(),
+        _ => {
+            let mut san = String::new();
+            let is_capture = matches!(piece_move.kind, MoveKind::Capture | MoveKind::EnPassant);
+            match kind {
+                PieceKind::Pawn => {
+                    if is_capture {
+                        san.push(piece_move.get_from_coordinates().column_char());
+                        san.push('x');
+                    }
+                    san.push_str(piece_move.get_to_coordinates().to_string().as_str());
+                    if let Some(promotion) = piece_move.promotion {
+                        san.push('=');
+                        san.push(promotion.to_char(WHITE));
+                    }
+                },
+                _ => {
+                    san.push(kind.to_char(WHITE));
+                    san.push_str(disambiguation(position, piece_move, kind, color).as_str());
+                    if is_capture {
+                        san.push('x');
+                    }
+                    san.push_str(piece_move.get_to_coordinates().to_string().as_str());
+                },
+            }
+            san
+        },
+    };
+
+    let mut position_after_move = *position;
+    position_after_move.make_move(piece_move);
+    if position_after_move.in_check(1 - color) {
+        match position_after_move.legal_moves().is_empty() {
+            true => san.push('#'),
+            false => san.push('+'),
+        }
+    }
+    san
+}
+
+// File, rank or both of the origin square, when another piece of the same kind
+// can reach the same destination.
+fn disambiguation(position: &Position, piece_move: &Move, kind: PieceKind, color: usize) -> String {
+    let from = piece_move.get_from_coordinates();
+    let rivals: Vec<Coordinates> = position.legal_moves().iter()
+        .filter(|other| other.to == piece_move.to
+            && other.from != piece_move.from
+            && position.get_piece(other.from) == Some((kind, color)))
+        .map(|other| other.get_from_coordinates())
+        .collect();
+
+    if rivals.is_empty() {
+        return String::new();
+    }
+    if rivals.iter().all(|rival| rival.column != from.column) {
+        return from.column_char().to_string();
+    }
+    if rivals.iter().all(|rival| rival.row != from.row) {
+        return from.row_char().to_string();
+    }
+    from.to_string()
+}
+
+pub fn san_to_move(position: &Position, san: &str) -> Result<Move, String> {
+    let cleaned = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = position.legal_moves();
+
+    let castle = match cleaned {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = castle {
+        return legal_moves.into_iter()
+            .find(|piece_move| piece_move.kind == MoveKind::Castle && (piece_move.to > piece_move.from) == king_side)
+            .ok_or(format!("Illegal move: {}", san));
+    }
+
+    // promotion is written as "e8=Q", but "e8Q" is accepted as well
+    let (body, promotion) = match cleaned.split_once('=') {
+        Some((body, promotion)) => {
+            let mut chars = promotion.chars();
+            match (chars.next().and_then(PieceKind::new_from_char), chars.next()) {
+                (Some((kind, _)), None) if !matches!(kind, PieceKind::Pawn | PieceKind::King) => (body, Some(kind)),
+                _ => return Err(format!("Invalid promotion piece: {}", san)),
+            }
+        },
+        None => match cleaned.chars().last() {
+            Some(symbol) if "QRBN".contains(symbol) => {
+                (&cleaned[..cleaned.len() - 1], PieceKind::new_from_char(symbol).map(|(kind, _)| kind))
+            },
+            _ => (cleaned, None),
+        },
+    };
+
+    let (kind, rest) = match body.chars().next() {
+        Some(symbol) if "KQRBN".contains(symbol) => (PieceKind::new_from_char(symbol).unwrap().0, &body[1..]),
+        _ => (PieceKind::Pawn, body),
+    };
+
+    let rest: String = rest.chars().filter(|c| !matches!(c, 'x' | ':' | '-')).collect();
+    if rest.len() < 2 || !rest.is_ascii() {
+        return Err(format!("Invalid move: {}", san));
+    }
+    let (qualifier, destination) = rest.split_at(rest.len() - 2);
+    let destination = match Coordinates::new_from_string(&destination.to_string()) {
+        Some(coordinates) if (0..8).contains(&coordinates.column) && (0..8).contains(&coordinates.row) => coordinates,
+        _ => return Err(format!("Invalid move: {}", san)),
+    };
+
+    let mut from_column: Option<i8> = None;
+    let mut from_row: Option<i8> = None;
+    for c in qualifier.chars() {
+        match c {
+            'a'..='h' => from_column = Some(c as i8 - 'a' as i8),
+            '1'..='8' => from_row = Some(c as i8 - '1' as i8),
+            _ => return Err(format!("Invalid move: {}", san)),
+        }
+    }
+
+    let side_to_move = position.get_side_to_move();
+    let candidates: Vec<Move> = legal_moves.into_iter()
+        .filter(|piece_move| {
+            let from = piece_move.get_from_coordinates();
+            position.get_piece(piece_move.from) == Some((kind, side_to_move))
+                && piece_move.kind != MoveKind::Castle
+                && piece_move.get_to_coordinates() == destination
+                && piece_move.promotion == promotion
+                && from_column.is_none_or(|column| column == from.column)
+                && from_row.is_none_or(|row| row == from.row)
+        })
+        .collect();
+
+    match candidates.len() {
+        0 => Err(format!("Illegal move: {}", san)),
+        1 => Ok(candidates[0]),
+        _ => Err(format!("Ambiguous move: {}", san)),
+    }
+}
@@ -0,0 +1,533 @@
+use pleco::Board as StockfishBoard;
+
+use crate::chess_engine::board::Board;
+use crate::chess_engine::coordinates::Coordinates;
+use crate::chess_engine::san::{move_to_san, san_to_move};
+use crate::game_end_condition::GameEndCondition;
+use crate::game_status::GameStatus;
+
+const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+fn board_from_fen(fen: &str) -> Board {
+    Board::new_from_fen("abcdefgh".to_string(), 8, "12345678".to_string(), 8, fen.to_string())
+}
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let board = board_from_fen(fen);
+    for (index, nodes) in expected.iter().enumerate() {
+        let depth = index as u32 + 1;
+        assert_eq!(board.perft(depth), *nodes, "perft({}) of {}", depth, fen);
+    }
+}
+
+fn pleco_divide(fen: &str, depth: u16) -> Vec<(String, u64)> {
+    let mut board = StockfishBoard::from_fen(fen).unwrap();
+    let mut nodes: Vec<(String, u64)> = board.generate_moves().iter().map(|bit_move| {
+        board.apply_move(*bit_move);
+        let count = pleco::board::perft::perft(&board, depth - 1);
+        board.undo_move();
+        (bit_move.stringify(), count)
+    }).collect();
+    nodes.sort();
+    nodes
+}
+
+fn make_moves(board: &mut Board, moves: &[(&str, &str)]) {
+    for (move_from, move_to) in moves {
+        assert!(
+            board.make_move_string(move_from.to_string(), move_to.to_string(), None),
+            "could not make move {}{}", move_from, move_to,
+        );
+    }
+}
+
+fn piece_symbol_at(board: &Board, square: &str) -> Option<String> {
+    let coordinates = Coordinates::new_from_string(&square.to_string()).unwrap();
+    board.get_pieces_dict().get(&coordinates).cloned().flatten().map(|piece| piece.get_symbol())
+}
+
+#[test]
+fn perft_start_position() {
+    assert_perft(START_POSITION, &[20, 400, 8902, 197281]);
+}
+
+#[test]
+fn perft_kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2039, 97862]);
+}
+
+#[test]
+fn perft_position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+}
+
+#[test]
+fn perft_position_4() {
+    assert_perft(POSITION_4, &[6, 264, 9467]);
+}
+
+#[test]
+fn perft_position_5() {
+    assert_perft(POSITION_5, &[44, 1486, 62379]);
+}
+
+#[test]
+fn perft_position_6() {
+    assert_perft(POSITION_6, &[46, 2079, 89890]);
+}
+
+#[test]
+fn perft_en_passant_traps() {
+    // en passant would expose the king along the rank
+    assert_perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", &[18, 92, 1670, 10138, 185429]);
+    // en passant would expose the king along the diagonal
+    assert_perft("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", &[13, 102, 1266, 10276, 135655]);
+    // en passant capture gives check
+    assert_perft("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", &[15, 126, 1928, 13931, 206379]);
+}
+
+#[test]
+fn perft_castling() {
+    assert_perft("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &[15, 66, 1198, 6399, 120330]);
+    assert_perft("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", &[16, 71, 1286, 7418, 141077]);
+    assert_perft("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", &[26, 1141, 27826]);
+    assert_perft("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", &[44, 1494, 50509]);
+}
+
+#[test]
+fn perft_promotion_traps() {
+    assert_perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", &[11, 133, 1442, 19174, 266199]);
+    assert_perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661, 38983, 217342]);
+    assert_perft("8/P1k5/K7/8/8/8/8/8 w - - 0 1", &[6, 27, 273, 1329, 18135, 92683]);
+}
+
+#[test]
+fn perft_stalemate_and_checkmate() {
+    assert_perft("K1k5/8/P7/8/8/8/8/8 w - - 0 1", &[2, 6, 13, 63, 382, 2217]);
+    assert_perft("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", &[10, 25, 268, 926, 10857, 43261]);
+    assert_perft("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", &[37, 183, 6559, 23527]);
+}
+
+#[test]
+fn divide_matches_pleco() {
+    let positions = [
+        START_POSITION,
+        KIWIPETE,
+        POSITION_3,
+        POSITION_4,
+        POSITION_5,
+        POSITION_6,
+        "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+    ];
+    for fen in positions {
+        assert_eq!(board_from_fen(fen).divide(3), pleco_divide(fen, 3), "divide(3) of {}", fen);
+    }
+}
+
+#[test]
+fn board_make_move_matches_position() {
+    // every legal move played through Board::make_move must produce the same
+    // position as the bitboard core
+    let positions = [KIWIPETE, POSITION_4, POSITION_5, "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1"];
+    for fen in positions {
+        let board = board_from_fen(fen);
+        let position = board.to_position();
+        let mut board = board;
+        for piece_move in position.legal_moves() {
+            let promotion_piece = piece_move.promotion
+                .map(|kind| kind.to_char(position.get_side_to_move()).to_string());
+            assert!(board.push_move(
+                &piece_move.get_from_coordinates(),
+                &piece_move.get_to_coordinates(),
+                promotion_piece,
+            ), "{} rejected in {}", piece_move, fen);
+
+            let mut expected = position;
+            expected.make_move(&piece_move);
+            assert_eq!(board.to_position(), expected, "{} in {}", piece_move, fen);
+
+            assert!(board.pop_move().is_some());
+            assert_eq!(board.to_position(), position, "{} taken back in {}", piece_move, fen);
+            assert_eq!(board.get_fen(), fen, "{} taken back", piece_move);
+        }
+    }
+}
+
+#[test]
+fn pawn_moves_one_or_two_squares_from_start() {
+    let board = board_from_fen(START_POSITION);
+    let mut moves = board.board_to_dict_by_active_color().get("e2").unwrap().1.clone();
+    moves.sort();
+    assert_eq!(moves, vec!["e3".to_string(), "e4".to_string()]);
+}
+
+#[test]
+fn pawn_promotion_requires_valid_piece() {
+    let mut board = board_from_fen("8/4P3/8/8/8/k7/8/4K3 w - - 0 1");
+    assert!(!board.make_move_string("e7".to_string(), "e8".to_string(), None));
+    assert!(!board.make_move_string("e7".to_string(), "e8".to_string(), Some("q".to_string())));
+    assert!(!board.make_move_string("e7".to_string(), "e8".to_string(), Some("K".to_string())));
+    assert_eq!(piece_symbol_at(&board, "e7"), Some("P".to_string()));
+
+    assert!(board.make_move_string("e7".to_string(), "e8".to_string(), Some("N".to_string())));
+    assert_eq!(piece_symbol_at(&board, "e8"), Some("N".to_string()));
+    assert_eq!(piece_symbol_at(&board, "e7"), None);
+}
+
+#[test]
+fn en_passant_capture_removes_pawn() {
+    let mut board = board_from_fen(START_POSITION);
+    make_moves(&mut board, &[("e2", "e4"), ("a7", "a6"), ("e4", "e5"), ("d7", "d5")]);
+    assert_eq!(board.get_en_passant_square(), "d6");
+
+    make_moves(&mut board, &[("e5", "d6")]);
+    assert_eq!(piece_symbol_at(&board, "d6"), Some("P".to_string()));
+    assert_eq!(piece_symbol_at(&board, "d5"), None);
+    assert_eq!(board.get_en_passant_square(), "-");
+}
+
+#[test]
+fn en_passant_expires_after_one_move() {
+    let mut board = board_from_fen(START_POSITION);
+    make_moves(&mut board, &[("e2", "e4"), ("a7", "a6"), ("e4", "e5"), ("d7", "d5"), ("g1", "f3"), ("h7", "h6")]);
+    assert!(!board.make_move_string("e5".to_string(), "d6".to_string(), None));
+}
+
+#[test]
+fn king_cannot_move_into_check() {
+    let board = board_from_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1");
+    let mut moves = board.board_to_dict_by_active_color().get("e1").unwrap().1.clone();
+    moves.sort();
+    assert_eq!(moves, vec!["d2".to_string(), "f1".to_string()]);
+}
+
+#[test]
+fn castle_rook_moves_rook_and_clears_rights() {
+    let mut board = board_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    make_moves(&mut board, &[("e1", "g1")]);
+    assert_eq!(piece_symbol_at(&board, "g1"), Some("K".to_string()));
+    assert_eq!(piece_symbol_at(&board, "f1"), Some("R".to_string()));
+    assert_eq!(piece_symbol_at(&board, "h1"), None);
+    assert_eq!(board.get_castle_options(), "kq");
+
+    make_moves(&mut board, &[("e8", "c8")]);
+    assert_eq!(piece_symbol_at(&board, "c8"), Some("k".to_string()));
+    assert_eq!(piece_symbol_at(&board, "d8"), Some("r".to_string()));
+    assert_eq!(piece_symbol_at(&board, "a8"), None);
+    assert_eq!(board.get_castle_options(), "");
+}
+
+#[test]
+fn castling_not_allowed_through_attacked_square() {
+    let board = board_from_fen("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1");
+    let king_moves = board.board_to_dict_by_active_color().get("e1").unwrap().1.clone();
+    assert!(!king_moves.contains(&"g1".to_string()));
+    assert!(king_moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn capturing_rook_removes_castle_right() {
+    let mut board = board_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    make_moves(&mut board, &[("a1", "a8")]);
+    assert_eq!(board.get_castle_options(), "Kk");
+}
+
+
+fn san_of(fen: &str, uci: &str) -> String {
+    let position = board_from_fen(fen).to_position();
+    let piece_move = position.legal_moves().into_iter()
+        .find(|piece_move| piece_move.to_string() == uci)
+        .unwrap();
+    move_to_san(&position, &piece_move)
+}
+
+#[test]
+fn san_encodes_pieces_captures_and_castling() {
+    assert_eq!(san_of(START_POSITION, "e2e4"), "e4");
+    assert_eq!(san_of(START_POSITION, "g1f3"), "Nf3");
+    assert_eq!(san_of(KIWIPETE, "e5f7"), "Nxf7");
+    assert_eq!(san_of(KIWIPETE, "d5e6"), "dxe6");
+    assert_eq!(san_of(KIWIPETE, "e1g1"), "O-O");
+    assert_eq!(san_of(KIWIPETE, "e1c1"), "O-O-O");
+    assert_eq!(san_of("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", "c4d3"), "cxd3+");
+}
+
+#[test]
+fn san_encodes_disambiguation() {
+    // knights on b1 and f3 can both reach d2
+    assert_eq!(san_of("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "b1d2"), "Nbd2");
+    // rooks on a1 and a5 can both reach a3
+    assert_eq!(san_of("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+    // queens on h4, e4 and h1 can all reach e1
+    assert_eq!(san_of("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "h4e1"), "Qh4e1");
+}
+
+#[test]
+fn san_encodes_promotion_check_and_mate() {
+    assert_eq!(san_of("8/4P3/8/8/8/k7/8/4K3 w - - 0 1", "e7e8q"), "e8=Q");
+    assert_eq!(san_of("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+    assert_eq!(san_of("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"), "e8=N");
+    assert_eq!(san_of("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8#");
+}
+
+#[test]
+fn san_decodes_every_legal_move() {
+    let positions = [START_POSITION, KIWIPETE, POSITION_3, POSITION_4, POSITION_5, POSITION_6];
+    for fen in positions {
+        let position = board_from_fen(fen).to_position();
+        for piece_move in position.legal_moves() {
+            let san = move_to_san(&position, &piece_move);
+            assert_eq!(san_to_move(&position, &san), Ok(piece_move), "{} in {}", san, fen);
+        }
+    }
+}
+
+#[test]
+fn san_decoder_rejects_illegal_and_ambiguous_moves() {
+    let position = board_from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").to_position();
+    assert!(san_to_move(&position, "Nd2").unwrap_err().starts_with("Ambiguous"));
+    assert!(san_to_move(&position, "Nd5").unwrap_err().starts_with("Illegal"));
+    assert!(san_to_move(&position, "O-O").is_err());
+    assert!(san_to_move(&position, "Zz9").is_err());
+    assert!(san_to_move(&position, "").is_err());
+    assert_eq!(san_to_move(&position, "Nf3d2").unwrap().to_string(), "f3d2");
+}
+
+#[test]
+fn board_records_san_history() {
+    let mut board = board_from_fen(START_POSITION);
+    for san in ["f3", "e5", "g4", "Qh4#"] {
+        assert!(board.make_move_san(san.to_string()), "could not make move {}", san);
+    }
+    assert_eq!(board.get_last_move_san(), Some("Qh4#".to_string()));
+    assert_eq!(board.get_moves_history(), "1. f3 e5 2. g4 Qh4# ");
+    assert!(!board.make_move_san("e4".to_string()));
+}
+
+fn load_with_moves_history(board: &Board, moves_history: &str) -> Result<Board, String> {
+    Board::new_from_db(
+        1,
+        board.get_fen(),
+        None,
+        board.get_pieces_dict(),
+        board.get_active_color().to_char(),
+        board.get_castle_options(),
+        board.get_en_passant_square(),
+        board.get_half_move_clock(),
+        board.get_full_move_number(),
+        8,
+        8,
+        "abcdefgh".to_string(),
+        "12345678".to_string(),
+        *board.get_moves_count(),
+        moves_history,
+    )
+}
+
+#[test]
+fn legacy_moves_history_is_converted_to_san_when_loading() {
+    let mut board = board_from_fen(START_POSITION);
+    make_moves(&mut board, &[("e2", "e4"), ("e7", "e5"), ("g1", "f3"), ("b8", "c6"), ("f1", "c4")]);
+
+    let loaded = load_with_moves_history(&board, "1 e2e4 e7e5  2 g1f3 b8c6  3 f1c4 ").unwrap();
+    assert_eq!(loaded.get_moves_history(), "1. e4 e5 2. Nf3 Nc6 3. Bc4 ");
+    assert_eq!(loaded.get_moves_history(), board.get_moves_history());
+    assert_eq!(loaded.get_ply_count(), 5);
+    assert_eq!(loaded.get_last_move_san(), Some("Bc4".to_string()));
+
+    assert_eq!(
+        load_with_moves_history(&board, "1 e2e4 e7e4 ").unwrap_err(),
+        "Could not convert move e7e4 of the legacy move history",
+    );
+}
+
+fn play_san(board: &mut Board, moves: &[&str]) {
+    for san in moves {
+        assert!(board.make_move_san(san.to_string()), "could not make move {}", san);
+    }
+}
+
+#[test]
+fn repetition_counts_knight_shuffles() {
+    let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+    let mut board = board_from_fen(START_POSITION);
+    assert_eq!(board.get_repetition_count(), 1);
+
+    play_san(&mut board, &shuffle);
+    assert_eq!(board.get_repetition_count(), 2);
+    assert!(!board.is_threefold_repetition());
+
+    play_san(&mut board, &shuffle);
+    assert!(board.is_threefold_repetition());
+    assert_eq!(board.get_game_status_and_end_condition().1, GameEndCondition::None);
+
+    play_san(&mut board, &shuffle);
+    play_san(&mut board, &shuffle);
+    assert_eq!(board.get_repetition_count(), 5);
+    let (game_status, game_end_condition) = board.get_game_status_and_end_condition();
+    assert_eq!(game_status, GameStatus::Finished);
+    assert_eq!(game_end_condition, GameEndCondition::FivefoldRepetition);
+}
+
+#[test]
+fn repetition_requires_same_castling_rights() {
+    let mut board = board_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    // the first king trip loses castling rights, so only later returns repeat
+    play_san(&mut board, &["Kf1", "Kf8", "Ke1", "Ke8"]);
+    assert_eq!(board.get_repetition_count(), 1);
+    play_san(&mut board, &["Kf1", "Kf8", "Ke1", "Ke8"]);
+    assert_eq!(board.get_repetition_count(), 2);
+}
+
+#[test]
+fn repetition_key_ignores_impossible_en_passant() {
+    let with_square = board_from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").to_position();
+    let without_square = board_from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").to_position();
+    assert_eq!(with_square.repetition_key(), without_square.repetition_key());
+
+    let with_capture = board_from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").to_position();
+    let without_capture = board_from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").to_position();
+    assert_ne!(with_capture.repetition_key(), without_capture.repetition_key());
+}
+
+#[test]
+fn half_move_clock_resets_on_pawn_moves_and_captures() {
+    let mut board = board_from_fen(START_POSITION);
+    play_san(&mut board, &["Nf3", "Nc6"]);
+    assert_eq!(board.get_half_move_clock(), 2);
+    assert_eq!(board.get_full_move_number(), 2);
+    play_san(&mut board, &["e4"]);
+    assert_eq!(board.get_half_move_clock(), 0);
+    play_san(&mut board, &["Nb4", "Bc4", "Nxa2"]);
+    assert_eq!(board.get_half_move_clock(), 0);
+    assert!(board.get_fen().ends_with(" 0 4"));
+}
+
+#[test]
+fn fifty_move_rule_is_claimable_and_seventy_five_ends_the_game() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 98 60");
+    play_san(&mut board, &["Ra3"]);
+    assert!(!board.is_fifty_move_rule());
+    play_san(&mut board, &["Kd7"]);
+    assert!(board.is_fifty_move_rule());
+    assert_eq!(board.get_game_status_and_end_condition().0, GameStatus::Ongoing);
+
+    let mut board = board_from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 149 90");
+    play_san(&mut board, &["Ra3"]);
+    assert_eq!(
+        board.get_game_status_and_end_condition(),
+        (GameStatus::Finished, GameEndCondition::SeventyFiveMoveRule)
+    );
+
+    // checkmate on the last move takes precedence
+    let mut board = board_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 149 90");
+    play_san(&mut board, &["Ra8#"]);
+    assert_eq!(board.get_game_status_and_end_condition().1, GameEndCondition::WhiteCheckmatedBlack);
+}
+
+#[test]
+fn insufficient_material_ends_the_game() {
+    for fen in [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+        "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1",
+    ] {
+        assert_eq!(
+            board_from_fen(fen).get_game_status_and_end_condition(),
+            (GameStatus::Finished, GameEndCondition::InsufficientMaterial),
+            "{}", fen
+        );
+    }
+
+    for fen in [
+        "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+        "1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+    ] {
+        assert_eq!(board_from_fen(fen).get_game_status_and_end_condition().0, GameStatus::Ongoing, "{}", fen);
+    }
+}
+
+#[test]
+fn capturing_the_last_pawn_can_leave_insufficient_material() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/3p4/2B1K3 w - - 0 1");
+    play_san(&mut board, &["Kxd2"]);
+    assert_eq!(board.get_game_status_and_end_condition().1, GameEndCondition::InsufficientMaterial);
+}
+
+// Plays the moves one at a time, then takes them back one at a time, checking
+// that every undo returns to exactly the fen and legal moves seen before.
+fn assert_undo_restores(fen: &str, moves: &[&str]) {
+    let mut board = board_from_fen(fen);
+    let mut snapshots = Vec::new();
+    for san in moves {
+        snapshots.push((board.get_fen(), board.get_moves_history().to_string(), board.board_to_dict_by_active_color()));
+        play_san(&mut board, &[san]);
+    }
+    assert_eq!(board.get_undo_count(), moves.len());
+
+    while let Some((fen, history, dict)) = snapshots.pop() {
+        assert!(board.pop_move().is_some());
+        assert_eq!(board.get_fen(), fen);
+        assert_eq!(board.get_moves_history(), history);
+        assert_eq!(board.board_to_dict_by_active_color(), dict);
+    }
+    assert!(board.pop_move().is_none());
+}
+
+#[test]
+fn undo_restores_captures_and_clocks() {
+    assert_undo_restores(START_POSITION, &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "Nf3", "Nf6"]);
+}
+
+#[test]
+fn undo_restores_castling_rights_and_rooks() {
+    assert_undo_restores(
+        "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1",
+        &["O-O", "O-O-O", "Rfe1", "Rhg8"],
+    );
+}
+
+#[test]
+fn undo_restores_en_passant_captures() {
+    assert_undo_restores(START_POSITION, &["e4", "a6", "e5", "d5", "exd6"]);
+}
+
+#[test]
+fn undo_restores_promotions() {
+    assert_undo_restores("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["axb8=Q+", "Ke7"]);
+}
+
+#[test]
+fn undo_restores_repetition_history() {
+    let mut board = board_from_fen(START_POSITION);
+    play_san(&mut board, &["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"]);
+    assert!(board.is_threefold_repetition());
+
+    assert!(board.pop_move().is_some());
+    assert!(!board.is_threefold_repetition());
+    play_san(&mut board, &["Ng8"]);
+    assert!(board.is_threefold_repetition());
+}
+
+#[test]
+fn popped_move_can_be_pushed_again() {
+    let mut board = board_from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    play_san(&mut board, &["axb8=N"]);
+    let fen = board.get_fen();
+
+    let (move_from, move_to, promotion_piece) = board.pop_move().unwrap();
+    assert_eq!(promotion_piece, Some("N".to_string()));
+    assert!(board.push_move(&move_from, &move_to, promotion_piece));
+    assert_eq!(board.get_fen(), fen);
+    assert_eq!(board.get_moves_history(), "1. axb8=N ");
+}
@@ -18,6 +18,10 @@ pub struct ConnectionManager {
     pub user_id_game_ids: Arc<DashMap<String, DashSet<Uuid>>>,
     pub ws_connection_id: Arc<DashMap<SocketAddr, Arc<Mutex<Tx>>>>,
     pub user_id_ws_connection_ids: Arc<DashMap<String, DashSet<SocketAddr>>>,
+    // read-only connections of non-players, kept apart from the players' connections
+    pub game_id_spectator_connection_ids: Arc<DashMap<Uuid, DashSet<SocketAddr>>>,
+    // connections following the seeks of the lobby
+    pub lobby_connection_ids: Arc<DashSet<SocketAddr>>,
 }
 
 impl ConnectionManager {
@@ -27,9 +31,94 @@ impl ConnectionManager {
             user_id_game_ids: Arc::new(DashMap::new()),
             ws_connection_id: Arc::new(DashMap::new()),
             user_id_ws_connection_ids: Arc::new(DashMap::new()),
+            game_id_spectator_connection_ids: Arc::new(DashMap::new()),
+            lobby_connection_ids: Arc::new(DashSet::new()),
         }
     }
 
+    pub fn add_spectator_connection(
+        &mut self,
+        game_id: &Uuid,
+        ws_connection_id: SocketAddr,
+        ws_connection: Arc<Mutex<Tx>>,
+    ) -> Result<String, String> {
+        if self.user_id_ws_connection_ids.iter().any(|connection_ids| connection_ids.contains(&ws_connection_id)) {
+            return Err("A player connection cannot spectate".to_string());
+        }
+        self.ws_connection_id.entry(ws_connection_id).or_insert(ws_connection);
+        self.game_id_spectator_connection_ids.entry(*game_id).or_default().insert(ws_connection_id);
+        Ok("Spectating game".to_string())
+    }
+
+    pub fn add_lobby_connection(&mut self, ws_connection_id: SocketAddr, ws_connection: Arc<Mutex<Tx>>) {
+        self.ws_connection_id.entry(ws_connection_id).or_insert(ws_connection);
+        self.lobby_connection_ids.insert(ws_connection_id);
+    }
+
+    // Connection waiting in the matchmaking queue, before it belongs to a game.
+    pub fn add_queue_connection(&mut self, ws_connection_id: SocketAddr, ws_connection: Arc<Mutex<Tx>>) {
+        self.ws_connection_id.entry(ws_connection_id).or_insert(ws_connection);
+    }
+
+    pub fn get_lobby_connection_ids(&self) -> Vec<SocketAddr> {
+        self.lobby_connection_ids.iter().map(|id| *id).collect()
+    }
+
+    pub fn is_spectator_connection(&self, ws_connection_id: &SocketAddr) -> bool {
+        self.game_id_spectator_connection_ids.iter().any(|connection_ids| connection_ids.contains(ws_connection_id))
+    }
+
+    pub fn is_spectator_connection_of_game(&self, game_id: &Uuid, ws_connection_id: &SocketAddr) -> bool {
+        self.game_id_spectator_connection_ids.get(game_id).is_some_and(|connection_ids| connection_ids.contains(ws_connection_id))
+    }
+
+    pub fn is_user_connection(&self, user_id: &str, ws_connection_id: &SocketAddr) -> bool {
+        self.user_id_ws_connection_ids.get(user_id).is_some_and(|connection_ids| connection_ids.contains(ws_connection_id))
+    }
+
+    pub fn get_user_connection_ids(&self, user_id: &str) -> Vec<SocketAddr> {
+        match self.user_id_ws_connection_ids.get(user_id) {
+            Some(connection_ids) => connection_ids.iter().map(|id| *id).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_spectator_count(&self, game_id: &Uuid) -> usize {
+        self.game_id_spectator_connection_ids.get(game_id).map_or(0, |connection_ids| connection_ids.len())
+    }
+
+    pub fn get_player_connection_ids(&self, game_id: &Uuid) -> Vec<SocketAddr> {
+        let mut connection_ids: Vec<SocketAddr> = Vec::new();
+        if let Some(user_ids) = self.game_id_user_ids.get(game_id) {
+            for user_id in user_ids.iter() {
+                if let Some(ids) = self.user_id_ws_connection_ids.get(user_id.key()) {
+                    connection_ids.extend(ids.iter().map(|id| *id));
+                }
+            }
+        }
+        connection_ids
+    }
+
+    pub fn get_spectator_connection_ids(&self, game_id: &Uuid) -> Vec<SocketAddr> {
+        match self.game_id_spectator_connection_ids.get(game_id) {
+            Some(connection_ids) => connection_ids.iter().map(|id| *id).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Forgets a closed connection, returns the games it was spectating.
+    pub fn remove_ws_connection(&mut self, ws_connection_id: &SocketAddr) -> Vec<Uuid> {
+        self.ws_connection_id.remove(ws_connection_id);
+        self.lobby_connection_ids.remove(ws_connection_id);
+        for connection_ids in self.user_id_ws_connection_ids.iter() {
+            connection_ids.remove(ws_connection_id);
+        }
+        self.game_id_spectator_connection_ids.iter()
+            .filter(|connection_ids| connection_ids.remove(ws_connection_id).is_some())
+            .map(|connection_ids| *connection_ids.key())
+            .collect()
+    }
+
     pub fn add_connection(
         &mut self,
         game_id: &Uuid,
@@ -108,4 +197,45 @@ impl ConnectionManager {
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use futures_channel::mpsc::unbounded;
+    use super::*;
+
+    fn connection() -> Arc<Mutex<Tx>> {
+        let (tx, _rx) = unbounded();
+        Arc::new(Mutex::new(tx))
+    }
+
+    #[test]
+    fn spectators_are_counted_apart_from_players() {
+        let mut connection_manager = ConnectionManager::new();
+        let game_id = Uuid::new_v4();
+        let player_address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let spectator_address: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+
+        let _ = connection_manager.add_connection(&game_id, &"alice".to_string(), Some(player_address), Some(connection()));
+        assert!(connection_manager.add_spectator_connection(&game_id, player_address, connection()).is_err());
+        assert!(connection_manager.add_spectator_connection(&game_id, spectator_address, connection()).is_ok());
+
+        assert_eq!(connection_manager.get_spectator_count(&game_id), 1);
+        assert!(connection_manager.is_spectator_connection(&spectator_address));
+        assert!(!connection_manager.is_spectator_connection(&player_address));
+        assert_eq!(connection_manager.game_id_user_ids.get(&game_id).unwrap().len(), 1);
+        assert_eq!(connection_manager.get_player_connection_ids(&game_id), vec![player_address]);
+        assert_eq!(connection_manager.get_spectator_connection_ids(&game_id), vec![spectator_address]);
+    }
+
+    #[test]
+    fn closed_spectator_connection_is_removed() {
+        let mut connection_manager = ConnectionManager::new();
+        let game_id = Uuid::new_v4();
+        let spectator_address: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+        let _ = connection_manager.add_spectator_connection(&game_id, spectator_address, connection());
+
+        assert_eq!(connection_manager.remove_ws_connection(&spectator_address), vec![game_id]);
+        assert_eq!(connection_manager.get_spectator_count(&game_id), 0);
+        assert!(connection_manager.ws_connection_id.get(&spectator_address).is_none());
+    }
+}
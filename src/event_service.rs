@@ -15,6 +15,7 @@ use crate::game_end_condition::GameEndCondition;
 use crate::game_manager::GameManager;
 use crate::game_status::GameStatus;
 use crate::response::Response;
+use crate::chat::ChatRoom;
 
 
 
@@ -40,7 +41,7 @@ pub enum Event {
 impl From<Response> for Event {
     fn from(value: Response) -> Self {
         match value {
-            Response::MakeMoveResponse { game_id, message, columns, rows, board, game_status, game_end_condition} =>
+            Response::MakeMoveResponse { game_id, message, columns, rows, board, game_status, game_end_condition, .. } =>
                 Event::MoveMade { game_id, message, columns, rows, board, game_status, game_end_condition },
             _ => Event::Default {},
         }
@@ -64,7 +65,7 @@ impl EventService{
     // Publish an event
     pub async fn publish(&self, response: &Response) {
         match response {
-            Response::CreateGameResponse { game_id, message } => {
+            Response::CreateGameResponse { game_id, message, .. } => {
                 println!("Game created with ID: {}.\n{}", game_id, message);
             },
 
@@ -73,8 +74,40 @@ impl EventService{
                 self.send_authorized_message(game_id.clone(), user_id.clone(), connection_id.clone(), board.clone(), message.clone()).await;
             },
 
-            Response::MakeMoveResponse { game_id, message, columns, rows, board, game_status, game_end_condition } => {
-                self.send_move_made_message(game_id.clone(), message.clone(), columns.clone(), rows.clone(), board.clone(), game_status.clone(), game_end_condition.clone()).await
+            Response::SeekAddedResponse { .. }
+            | Response::SeekRemovedResponse { .. } => {
+                let connection_ids = self.game_manager.read().await.connection_manager.get_lobby_connection_ids();
+                self.send_to_connection_ids(connection_ids, response).await
+            },
+
+            // to the connections of both users, wherever they are authorized
+            Response::ChallengeResponse { challenger_id, challenged_user_id, .. } => {
+                let connection_ids = {
+                    let connection_manager = &self.game_manager.read().await.connection_manager;
+                    [challenger_id, challenged_user_id].iter()
+                        .flat_map(|user_id| connection_manager.get_user_connection_ids(user_id))
+                        .collect()
+                };
+                self.send_to_connection_ids(connection_ids, response).await
+            },
+
+            Response::ChatMessageResponse { game_id, room, .. } => {
+                self.send_to_chat_room(*game_id, room, response).await
+            },
+
+            Response::SpectateGameResponse { connection_id, .. }
+            | Response::ChatHistoryResponse { connection_id, .. }
+            | Response::QueueResponse { connection_id, .. }
+            | Response::MatchFoundResponse { connection_id, .. } => {
+                self.send_to_connection(connection_id, response).await
+            },
+
+            Response::MakeMoveResponse { game_id, .. }
+            | Response::GameEndedResponse { game_id, .. }
+            | Response::DrawOfferResponse { game_id, .. }
+            | Response::TakebackResponse { game_id, .. }
+            | Response::SpectatorCountResponse { game_id, .. } => {
+                self.send_to_game_connections(game_id.clone(), response).await
             },
             _ => {},
         }
@@ -126,49 +159,47 @@ impl EventService{
         };
     }
 
-    async fn send_move_made_message(
-        &self,
-        game_id: Uuid,
-        message: String,
-        columns: String,
-        rows: String,
-        board: HashMap<String, (String, Vec<String>)>,
-        game_status: GameStatus,
-        game_end_condition: GameEndCondition,
-    ) {
-        let game_manager_lock = self.game_manager.read().await;
-        match game_manager_lock.connection_manager.game_id_user_ids.get(&game_id) {
-            Some(user_ids) => {
-                let mut ws_connection_ids: Vec<SocketAddr> = Vec::new();
-                for user_id in user_ids.clone() {
-                    match game_manager_lock.connection_manager.user_id_ws_connection_ids.get(&user_id) {
-                        Some(ids) => {
-                            let ws_ids = ids.value();
-                            ws_ids.iter().for_each(|item| ws_connection_ids.push(*item));
-                        },
-                        _ => {},
-                    }
-                }
+    async fn send_to_connection(&self, connection_id: &SocketAddr, response: &Response) {
+        self.send_to_connection_ids(vec![*connection_id], response).await
+    }
 
-                let mut connections: Vec<Arc<Mutex<Tx>>> = Vec::new();
-                for id in ws_connection_ids {
-                    match game_manager_lock.connection_manager.ws_connection_id.get(&id) {
-                        Some(connection) => connections.push(connection.value().clone()),
-                        _ => {},
-                    }
-                }
-                let response = Response::MakeMoveResponse { game_id, message, columns, rows, board, game_status, game_end_condition };
-                // let response = serde_json::to_string(&response).unwrap();
-                for mut connection in connections {
-                    let mut ws_connection = connection.lock().await;
-                    let response_text = serde_json::to_string(&response).unwrap();
-                    let e = ws_connection.unbounded_send(Message::text(response_text.clone()));
-
-                    // let message = tokio_websockets::Message::text(response.clone());
-                    // connection.lock().await.send(message).await.unwrap();
-                }
-            },
-            _ => {},
+    // Sends the response to every connection of every user in the game and to its spectators.
+    async fn send_to_game_connections(&self, game_id: Uuid, response: &Response) {
+        let connection_ids = {
+            let connection_manager = &self.game_manager.read().await.connection_manager;
+            let mut connection_ids = connection_manager.get_player_connection_ids(&game_id);
+            connection_ids.extend(connection_manager.get_spectator_connection_ids(&game_id));
+            connection_ids
         };
+        self.send_to_connection_ids(connection_ids, response).await
+    }
+
+    // Player chat goes to the players' connections, spectator chat to the spectators'.
+    async fn send_to_chat_room(&self, game_id: Uuid, room: &ChatRoom, response: &Response) {
+        let connection_ids = {
+            let connection_manager = &self.game_manager.read().await.connection_manager;
+            match room {
+                ChatRoom::Players => connection_manager.get_player_connection_ids(&game_id),
+                ChatRoom::Spectators => connection_manager.get_spectator_connection_ids(&game_id),
+            }
+        };
+        self.send_to_connection_ids(connection_ids, response).await
+    }
+
+    async fn send_to_connection_ids(&self, connection_ids: Vec<SocketAddr>, response: &Response) {
+        let connections: Vec<Arc<Mutex<Tx>>> = {
+            let game_manager_lock = self.game_manager.read().await;
+            connection_ids.iter()
+                .filter_map(|id| game_manager_lock.connection_manager.ws_connection_id.get(id))
+                .map(|connection| connection.value().clone())
+                .collect()
+        };
+
+        let response_text = serde_json::to_string(response).unwrap();
+        for connection in connections {
+            if let Err(e) = connection.lock().await.unbounded_send(Message::text(response_text.clone())) {
+                println!("Failed to send message to WebSocket connection: {}", e);
+            }
+        }
     }
 }
\ No newline at end of file
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
 use uuid::Uuid;
 
 use crate::game_status::GameStatus;
-use crate::chess_engine::board::Board;
+use crate::chess_engine::board::{Board, INITIAL_FEN};
 use crate::game_end_condition::GameEndCondition;
-use crate::chess_engine::color::ActiveColor;
+use crate::chess_engine::color::{ActiveColor, Color};
 use crate::chess_engine::coordinates::Coordinates;
+use crate::time_control::{Clock, TimeControl};
+use crate::rating::RatingCategory;
 
 #[derive(Clone, Debug)]
 pub struct Game {
@@ -19,30 +22,46 @@ pub struct Game {
     status: GameStatus,
     game_end_condition: GameEndCondition,
     board: Board,
+    // side with a pending draw offer
+    draw_offer: Option<ActiveColor>,
+    // side with a pending takeback request
+    takeback_request: Option<ActiveColor>,
+    // untimed games have no clock
+    clock: Option<Clock>,
+    // correspondence games give each side days per move instead of a clock
+    days_per_move: Option<u32>,
+    move_deadline: Option<SystemTime>,
+    // since when the game waits for one of the first two moves
+    first_move_wait_started: Option<Instant>,
+    rated: bool,
+    // white, black rating change, set once a rated game is rated
+    rating_diffs: Option<(i32, i32)>,
+    // private games are joined with the secret token instead of from the lobby
+    join_token: Option<String>,
+    // only this user may join a direct challenge
+    challenged_user_id: Option<String>,
 }
 
 impl Game {
-    pub fn new(user_id: String, color: String) -> Game {
+    pub fn new(user_id: String, color: Color) -> Game {
         let mut color_by_user_id = HashMap::new();
-        let (white_id, black_id) = match color.as_str() {
-            "white" => {
+        // a random color is resolved when the opponent joins
+        let (white_id, black_id) = match color {
+            Color::White => {
                 color_by_user_id.insert(user_id.clone(), ActiveColor::White);
                 (Some(user_id.clone()), None)
             },
-            "black" => {
+            Color::Black => {
                 color_by_user_id.insert(user_id.clone(), ActiveColor::Black);
                 (None, Some(user_id.clone()))
             },
-            _ => {
-                color_by_user_id.insert(user_id.clone(), ActiveColor::White);
-                (Some(user_id.clone()), None)
-            },
+            Color::Random => (None, None),
         };
 
         let columns = "abcdefgh".to_string();
         let rows = "12345678".to_string();
         let board_size = 8;
-        let board_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string();
+        let board_fen = INITIAL_FEN.to_string();
         // let board_fen = "rnbq1bnr/pppppppp/3k4/8/2K5/8/PPPPPPPP/RNBQ1BNR w KQkq - 0 1".to_string();
 
         // checks, castles
@@ -71,12 +90,22 @@ impl Game {
             game_end_condition: GameEndCondition::None,
             board_id: None,
             board,
+            draw_offer: None,
+            takeback_request: None,
+            clock: None,
+            days_per_move: None,
+            move_deadline: None,
+            first_move_wait_started: None,
+            rated: false,
+            rating_diffs: None,
+            join_token: None,
+            challenged_user_id: None,
         };
 
         game
     }
 
-    pub fn create_game_from_board(user_id: String, board: Board, color: String) -> Game {
+    pub fn create_game_from_board(user_id: String, board: Board, color: Color) -> Game {
         let mut game = Game::new(user_id, color);
         game.set_board(board);
         game
@@ -114,6 +143,16 @@ impl Game {
             board,
             status,
             game_end_condition,
+            draw_offer: None,
+            takeback_request: None,
+            clock: None,
+            days_per_move: None,
+            move_deadline: None,
+            first_move_wait_started: None,
+            rated: false,
+            rating_diffs: None,
+            join_token: None,
+            challenged_user_id: None,
         }
     }
 
@@ -145,23 +184,90 @@ impl Game {
         match user2_id {
             Some(user_id) => {
                 self.user2_id = Some(user_id.clone());
-                match (&self.white_id, &self.black_id) {
-                    (None, _) => {
-                        self.color_by_user_id.insert(user_id.clone(), ActiveColor::White);
-                        self.white_id = Some(user_id.clone())
-                    },
-                    (_, None) => self.black_id = {
-                        self.color_by_user_id.insert(user_id.clone(), ActiveColor::Black);
-                        Some(user_id.clone())
-                    },
-                    _ => (),
+                let joining_color = match (&self.white_id, &self.black_id) {
+                    // the creator asked for a random color
+                    (None, None) => Some(Color::Random.resolve()),
+                    (None, _) => Some(ActiveColor::White),
+                    (_, None) => Some(ActiveColor::Black),
+                    _ => None,
+                };
+                if let Some(joining_color) = joining_color {
+                    if let Some(creator_id) = self.user1_id.clone() {
+                        if !self.color_by_user_id.contains_key(&creator_id) {
+                            self.assign_color(&creator_id, joining_color.next());
+                        }
+                    }
+                    self.assign_color(&user_id, joining_color);
                 }
                 self.status = GameStatus::Ongoing;
+                self.reset_move_deadline();
+                self.first_move_wait_started = Some(Instant::now());
             },
             None => (),
         }
     }
 
+    fn assign_color(&mut self, user_id: &str, color: ActiveColor) {
+        match color {
+            ActiveColor::White => self.white_id = Some(user_id.to_string()),
+            ActiveColor::Black => self.black_id = Some(user_id.to_string()),
+        }
+        self.color_by_user_id.insert(user_id.to_string(), color);
+    }
+
+    // Makes the game private, returns the token to share.
+    pub fn create_join_token(&mut self) -> String {
+        let join_token = Uuid::new_v4().simple().to_string();
+        self.join_token = Some(join_token.clone());
+        join_token
+    }
+
+    pub fn set_challenged_user_id(&mut self, challenged_user_id: Option<String>) {
+        self.challenged_user_id = challenged_user_id;
+    }
+
+    pub fn get_challenged_user_id(&self) -> Option<String> {
+        self.challenged_user_id.clone()
+    }
+
+    // Private games and challenges are not listed in the lobby.
+    pub fn is_private(&self) -> bool {
+        self.join_token.is_some() || self.challenged_user_id.is_some()
+    }
+
+    pub fn can_join(&self, user_id: &str, join_token: Option<&str>) -> Result<(), String> {
+        if self.status != GameStatus::AwaitingOpponent || self.user2_id.is_some() {
+            return Err("Game is not awaiting an opponent".to_string());
+        }
+        if self.user1_id.as_deref() == Some(user_id) {
+            return Err("Cannot join your own game".to_string());
+        }
+        if self.challenged_user_id.as_ref().is_some_and(|challenged_user_id| challenged_user_id != user_id) {
+            return Err("The challenge is for another user".to_string());
+        }
+        if self.join_token.is_some() && self.join_token.as_deref() != join_token {
+            return Err("Wrong join token".to_string());
+        }
+        Ok(())
+    }
+
+    // The challenged user turns the challenge down, the game ends unplayed.
+    pub fn decline_challenge(&mut self, user_id: &str) -> Result<(), String> {
+        if self.challenged_user_id.as_deref() != Some(user_id) {
+            return Err("No challenge to decline".to_string());
+        }
+        if self.status != GameStatus::AwaitingOpponent {
+            return Err("The challenge is no longer open".to_string());
+        }
+        self.set_game_status_and_end_condition(GameStatus::Aborted, GameEndCondition::None);
+        Ok(())
+    }
+
+    // The creator is a player before a color is assigned to them.
+    pub fn is_player(&self, user_id: &str) -> bool {
+        self.user1_id.as_deref() == Some(user_id) || self.user2_id.as_deref() == Some(user_id)
+    }
+
     pub fn get_user1_id(&self) -> Option<String> {
         self.user1_id.clone()
     }
@@ -205,6 +311,338 @@ impl Game {
         (self.status, self.game_end_condition) = self.board.get_game_status_and_end_condition();
     }
 
+    pub fn set_game_status_and_end_condition(&mut self, status: GameStatus, game_end_condition: GameEndCondition) {
+        self.status = status;
+        self.game_end_condition = game_end_condition;
+        self.stop_timing_if_finished();
+    }
+
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.clock = Some(Clock::new(time_control));
+    }
+
+    pub fn get_clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    pub fn get_time_control(&self) -> Option<&TimeControl> {
+        self.clock.as_ref().map(|clock| clock.get_time_control())
+    }
+
+    // Remaining white and black time, None for untimed games.
+    pub fn get_remaining_times_ms(&self) -> (Option<u64>, Option<u64>) {
+        let now = Instant::now();
+        match &self.clock {
+            Some(clock) => (
+                Some(clock.get_remaining_ms(&ActiveColor::White, now)),
+                Some(clock.get_remaining_ms(&ActiveColor::Black, now)),
+            ),
+            None => (None, None),
+        }
+    }
+
+    pub fn set_days_per_move(&mut self, days_per_move: u32) {
+        self.days_per_move = Some(days_per_move);
+        self.reset_move_deadline();
+    }
+
+    pub fn get_days_per_move(&self) -> Option<u32> {
+        self.days_per_move
+    }
+
+    pub fn set_rated(&mut self, rated: bool) {
+        self.rated = rated;
+    }
+
+    pub fn is_rated(&self) -> bool {
+        self.rated
+    }
+
+    // Untimed games have no category and cannot be rated.
+    pub fn get_rating_category(&self) -> Option<RatingCategory> {
+        match (self.days_per_move, self.get_time_control()) {
+            (Some(_), _) => Some(RatingCategory::Correspondence),
+            (None, Some(time_control)) => Some(RatingCategory::new_from_time_control(time_control)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn get_rating_diffs(&self) -> Option<(i32, i32)> {
+        self.rating_diffs
+    }
+
+    pub fn set_rating_diffs(&mut self, rating_diffs: Option<(i32, i32)>) {
+        self.rating_diffs = rating_diffs;
+    }
+
+    pub fn get_move_deadline(&self) -> Option<SystemTime> {
+        self.move_deadline
+    }
+
+    pub fn set_move_deadline(&mut self, move_deadline: Option<SystemTime>) {
+        self.move_deadline = move_deadline;
+    }
+
+    // The side to move of an ongoing correspondence game gets a new deadline.
+    fn reset_move_deadline(&mut self) {
+        self.move_deadline = match (self.days_per_move, &self.status) {
+            (Some(days), GameStatus::Ongoing) => Some(SystemTime::now() + Duration::from_secs(days as u64 * 24 * 60 * 60)),
+            _ => None,
+        };
+    }
+
+    pub fn is_out_of_time(&self) -> bool {
+        self.clock.as_ref().is_some_and(|clock| clock.get_flagged_color(Instant::now()).is_some())
+            || self.move_deadline.is_some_and(|move_deadline| move_deadline <= SystemTime::now())
+    }
+
+    // Ends the game when the side to move has run out of time.
+    pub fn flag_if_out_of_time(&mut self, now: Instant) -> bool {
+        match self.clock.as_ref().and_then(|clock| clock.get_flagged_color(now)) {
+            Some(color) => self.lose_on_time(color),
+            None => false,
+        }
+    }
+
+    // Ends the correspondence game when the side to move has missed the deadline.
+    pub fn flag_if_past_deadline(&mut self, now: SystemTime) -> bool {
+        match self.move_deadline {
+            Some(move_deadline) if move_deadline <= now => self.lose_on_time(self.get_active_color()),
+            _ => false,
+        }
+    }
+
+    fn lose_on_time(&mut self, color: ActiveColor) -> bool {
+        if self.status != GameStatus::Ongoing {
+            return false;
+        }
+        let game_end_condition = match color {
+            ActiveColor::White => GameEndCondition::BlackWonOnTime,
+            ActiveColor::Black => GameEndCondition::WhiteWonOnTime,
+        };
+        self.set_game_status_and_end_condition(GameStatus::Finished, game_end_condition);
+        true
+    }
+
+    fn stop_timing_if_finished(&mut self) {
+        if self.status == GameStatus::Ongoing || self.status == GameStatus::AwaitingOpponent {
+            return;
+        }
+        self.move_deadline = None;
+        self.first_move_wait_started = None;
+        if let Some(clock) = &mut self.clock {
+            clock.stop(Instant::now());
+        }
+    }
+
+    pub fn get_draw_offer(&self) -> Option<ActiveColor> {
+        self.draw_offer.clone()
+    }
+
+    pub fn offer_draw(&mut self, user_id: &str) -> Result<(), String> {
+        let color = self.get_ongoing_game_player_color(user_id)?;
+        match &self.draw_offer {
+            Some(offering_color) if offering_color.equals(color.clone()) => Err("Draw already offered".to_string()),
+            Some(_) => Err("Opponent has already offered a draw".to_string()),
+            None => {
+                self.draw_offer = Some(color);
+                Ok(())
+            },
+        }
+    }
+
+    pub fn accept_draw(&mut self, user_id: &str) -> Result<GameEndCondition, String> {
+        self.take_opponent_draw_offer(user_id)?;
+        self.set_game_status_and_end_condition(GameStatus::Finished, GameEndCondition::Draw);
+        Ok(GameEndCondition::Draw)
+    }
+
+    pub fn decline_draw(&mut self, user_id: &str) -> Result<(), String> {
+        self.take_opponent_draw_offer(user_id)
+    }
+
+    fn take_opponent_draw_offer(&mut self, user_id: &str) -> Result<(), String> {
+        let color = self.get_ongoing_game_player_color(user_id)?;
+        match &self.draw_offer {
+            Some(offering_color) if !offering_color.equals(color) => {
+                self.draw_offer = None;
+                Ok(())
+            },
+            _ => Err("No draw offer to answer".to_string()),
+        }
+    }
+
+    fn get_ongoing_game_player_color(&self, user_id: &str) -> Result<ActiveColor, String> {
+        if self.status != GameStatus::Ongoing {
+            return Err("Game is not ongoing".to_string());
+        }
+        self.color_by_user_id.get(user_id).cloned().ok_or("Wrong user id".to_string())
+    }
+
+    pub fn get_takeback_request(&self) -> Option<ActiveColor> {
+        self.takeback_request.clone()
+    }
+
+    // Takebacks would change the result of rated games, so only casual games allow them.
+    pub fn request_takeback(&mut self, user_id: &str) -> Result<(), String> {
+        let color = self.get_ongoing_game_player_color(user_id)?;
+        if self.rated {
+            return Err("Takebacks are only allowed in casual games".to_string());
+        }
+        if self.takeback_request.is_some() {
+            return Err("Takeback already requested".to_string());
+        }
+        if self.board.get_undo_count() < self.get_takeback_ply_count(&color) {
+            return Err("No move to take back".to_string());
+        }
+        self.takeback_request = Some(color);
+        Ok(())
+    }
+
+    // Takes back the requesting side's last move, and the opponent's reply if
+    // there was one. Returns the number of moves taken back.
+    pub fn accept_takeback(&mut self, user_id: &str) -> Result<usize, String> {
+        if self.rated {
+            return Err("Takebacks are only allowed in casual games".to_string());
+        }
+        let requesting_color = self.take_opponent_takeback_request(user_id)?;
+        let ply_count = self.get_takeback_ply_count(&requesting_color);
+        if self.board.get_undo_count() < ply_count {
+            return Err("No move to take back".to_string());
+        }
+
+        let now = Instant::now();
+        for _ in 0..ply_count {
+            self.board.pop_move();
+            if let Some(clock) = &mut self.clock {
+                clock.take_back(&self.board.get_active_color(), now);
+            }
+        }
+        self.draw_offer = None;
+        self.update_game_status_and_end_condition();
+        self.reset_move_deadline();
+        self.first_move_wait_started = match self.board.get_ply_count() < 2 {
+            true => Some(now),
+            false => None,
+        };
+        Ok(ply_count)
+    }
+
+    pub fn decline_takeback(&mut self, user_id: &str) -> Result<(), String> {
+        self.take_opponent_takeback_request(user_id).map(|_| ())
+    }
+
+    fn take_opponent_takeback_request(&mut self, user_id: &str) -> Result<ActiveColor, String> {
+        let color = self.get_ongoing_game_player_color(user_id)?;
+        match self.takeback_request.clone() {
+            Some(requesting_color) if !requesting_color.equals(color) => {
+                self.takeback_request = None;
+                Ok(requesting_color)
+            },
+            _ => Err("No takeback request to answer".to_string()),
+        }
+    }
+
+    // Moves to undo so that the requesting side is to move again.
+    fn get_takeback_ply_count(&self, requesting_color: &ActiveColor) -> usize {
+        match self.board.get_active_color().equals(requesting_color.clone()) {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    // A game can be aborted until both sides have made their first move.
+    pub fn can_abort(&self) -> bool {
+        match self.status {
+            GameStatus::AwaitingOpponent => true,
+            GameStatus::Ongoing => self.board.get_ply_count() < 2,
+            _ => false,
+        }
+    }
+
+    pub fn abort(&mut self, user_id: &str) -> Result<GameEndCondition, String> {
+        if !self.is_player(user_id) {
+            return Err("Wrong user id".to_string());
+        }
+        if !self.can_abort() {
+            return Err("Game can only be aborted before both sides have moved".to_string());
+        }
+        self.set_game_status_and_end_condition(GameStatus::Aborted, GameEndCondition::None);
+        Ok(GameEndCondition::None)
+    }
+
+    // One of the first two moves was not made within the window. Correspondence
+    // games only end on their move deadline.
+    pub fn is_first_move_missed(&self, now: Instant, window: Duration) -> bool {
+        if self.days_per_move.is_some() {
+            return false;
+        }
+        match self.first_move_wait_started {
+            Some(started) => self.can_abort() && now.saturating_duration_since(started) >= window,
+            None => false,
+        }
+    }
+
+    pub fn abort_if_first_move_missed(&mut self, now: Instant, window: Duration) -> bool {
+        if !self.is_first_move_missed(now, window) {
+            return false;
+        }
+        self.set_game_status_and_end_condition(GameStatus::Aborted, GameEndCondition::None);
+        true
+    }
+
+    pub fn resign(&mut self, user_id: &str) -> Result<GameEndCondition, String> {
+        let game_end_condition = match self.get_ongoing_game_player_color(user_id)? {
+            ActiveColor::White => GameEndCondition::WhiteResigned,
+            ActiveColor::Black => GameEndCondition::BlackResigned,
+        };
+        self.set_game_status_and_end_condition(GameStatus::Finished, game_end_condition.clone());
+        Ok(game_end_condition)
+    }
+
+    // Ends the game as a draw if the current position allows a claim.
+    pub fn claim_draw(&mut self) -> Result<GameEndCondition, String> {
+        if self.status != GameStatus::Ongoing {
+            return Err("Game is not ongoing".to_string());
+        }
+        if self.board.is_threefold_repetition() {
+            self.set_game_status_and_end_condition(GameStatus::Finished, GameEndCondition::ThreefoldRepetition);
+            return Ok(GameEndCondition::ThreefoldRepetition);
+        }
+        if self.board.is_fifty_move_rule() {
+            self.set_game_status_and_end_condition(GameStatus::Finished, GameEndCondition::FiftyMoveRule);
+            return Ok(GameEndCondition::FiftyMoveRule);
+        }
+        Err("No draw can be claimed in this position".to_string())
+    }
+
+    // Presses the mover's clock. A move by the opponent of the offering side
+    // declines the draw offer, any move cancels a takeback request.
+    fn after_move(&mut self, mover: &ActiveColor, move_made: bool) {
+        if move_made {
+            if let Some(clock) = &mut self.clock {
+                clock.press(mover, Instant::now());
+            }
+        }
+        self.update_game_status_and_end_condition();
+        if move_made {
+            self.reset_move_deadline();
+            self.first_move_wait_started = match self.board.get_ply_count() < 2 {
+                true => Some(Instant::now()),
+                false => None,
+            };
+        }
+        self.stop_timing_if_finished();
+        if let Some(offering_color) = &self.draw_offer {
+            if move_made && !offering_color.equals(mover.clone()) {
+                self.draw_offer = None;
+            }
+        }
+        if move_made {
+            self.takeback_request = None;
+        }
+    }
+
     pub fn make_move(
         &mut self,
         move_from: &Coordinates,
@@ -212,20 +650,277 @@ impl Game {
         calculate_new_moves: bool,
         promotion_piece: Option<String>
     ) -> bool {
+        let mover = self.get_active_color();
         let result = self.board.make_move(move_from, move_to, calculate_new_moves, promotion_piece);
-        self.update_game_status_and_end_condition();
+        self.after_move(&mover, result);
         result
     }
 
     pub fn make_move_string(&mut self, move_from: String, move_to: String, promotion_piece: Option<String>) -> bool {
+        let mover = self.get_active_color();
         let result = self.board.make_move_string(move_from, move_to, promotion_piece);
-        self.update_game_status_and_end_condition();
+        self.after_move(&mover, result);
+        result
+    }
+
+    pub fn make_move_san(&mut self, san: String) -> bool {
+        let mover = self.get_active_color();
+        let result = self.board.make_move_san(san);
+        self.after_move(&mover, result);
         result
     }
 
     pub fn make_move_chars(&mut self, move_from: (char, char), move_to: (char, char), promotion_piece: Option<String>) -> bool {
+        let mover = self.get_active_color();
         let result = self.board.make_move_chars(move_from, move_to, promotion_piece);
-        self.update_game_status_and_end_condition();
+        self.after_move(&mover, result);
         result
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ongoing_game() -> Game {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_user(None, Some("bob".to_string()));
+        game
+    }
+
+    #[test]
+    fn random_color_is_resolved_when_the_opponent_joins() {
+        let mut seen_white = [false, false];
+        for _ in 0..64 {
+            let mut game = Game::new("alice".to_string(), Color::Random);
+            assert!(game.color_by_user_id.is_empty());
+            assert!(game.is_player("alice"));
+            game.set_user(None, Some("bob".to_string()));
+
+            let alice = game.color_by_user_id.get("alice").cloned().unwrap();
+            let bob = game.color_by_user_id.get("bob").cloned().unwrap();
+            assert!(alice.next().equals(bob));
+            match alice {
+                ActiveColor::White => seen_white[0] = true,
+                ActiveColor::Black => seen_white[1] = true,
+            }
+            assert!(game.get_white_id().is_some() && game.get_black_id().is_some());
+        }
+        assert_eq!(seen_white, [true, true]);
+    }
+
+    #[test]
+    fn random_color_game_can_be_aborted_before_the_opponent_joins() {
+        let mut game = Game::new("alice".to_string(), Color::Random);
+        assert!(game.abort("bob").is_err());
+        assert_eq!(game.abort("alice"), Ok(GameEndCondition::None));
+    }
+
+    #[test]
+    fn color_must_be_white_black_or_random() {
+        assert_eq!(serde_json::from_str::<Color>("\"random\"").unwrap(), Color::Random);
+        let error = serde_json::from_str::<Color>("\"purple\"").unwrap_err().to_string();
+        assert!(error.contains("expected one of `white`, `black`, `random`"), "{}", error);
+    }
+
+    #[test]
+    fn resigning_player_loses() {
+        let mut game = ongoing_game();
+        assert_eq!(game.resign("carol"), Err("Wrong user id".to_string()));
+        assert_eq!(game.resign("bob"), Ok(GameEndCondition::BlackResigned));
+        assert_eq!(game.get_game_status(), GameStatus::Finished);
+        assert_eq!(game.resign("alice"), Err("Game is not ongoing".to_string()));
+    }
+
+    #[test]
+    fn resigning_requires_an_opponent() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        assert!(game.resign("alice").is_err());
+    }
+
+    #[test]
+    fn draw_is_claimable_after_threefold_repetition() {
+        let mut game = ongoing_game();
+        assert!(game.claim_draw().is_err());
+        for _ in 0..2 {
+            for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                assert!(game.make_move_san(san.to_string()));
+            }
+        }
+        assert_eq!(game.claim_draw(), Ok(GameEndCondition::ThreefoldRepetition));
+        assert_eq!(game.get_game_status(), GameStatus::Finished);
+    }
+
+    #[test]
+    fn draw_offer_can_be_accepted_by_the_opponent_only() {
+        let mut game = ongoing_game();
+        assert!(game.accept_draw("bob").is_err());
+        assert_eq!(game.offer_draw("alice"), Ok(()));
+        assert_eq!(game.offer_draw("alice"), Err("Draw already offered".to_string()));
+        assert!(game.accept_draw("alice").is_err());
+        assert_eq!(game.accept_draw("bob"), Ok(GameEndCondition::Draw));
+        assert_eq!(game.get_game_status(), GameStatus::Finished);
+    }
+
+    #[test]
+    fn draw_offer_expires_when_the_opponent_moves() {
+        let mut game = ongoing_game();
+        assert_eq!(game.offer_draw("alice"), Ok(()));
+        assert!(game.make_move_san("e4".to_string()));
+        assert!(game.get_draw_offer().is_some());
+        assert!(game.make_move_san("e5".to_string()));
+        assert!(game.get_draw_offer().is_none());
+        assert!(game.accept_draw("bob").is_err());
+
+        assert_eq!(game.offer_draw("bob"), Ok(()));
+        assert_eq!(game.decline_draw("alice"), Ok(()));
+        assert!(game.get_draw_offer().is_none());
+    }
+
+    #[test]
+    fn takeback_undoes_the_requesting_sides_last_move() {
+        let mut game = ongoing_game();
+        assert_eq!(game.request_takeback("alice"), Err("No move to take back".to_string()));
+        assert!(game.make_move_san("e4".to_string()));
+        assert_eq!(game.request_takeback("alice"), Ok(()));
+        assert!(game.accept_takeback("alice").is_err());
+        assert_eq!(game.accept_takeback("bob"), Ok(1));
+        assert_eq!(game.get_board().get_ply_count(), 0);
+        assert!(game.get_active_color().equals(ActiveColor::White));
+
+        for san in ["e4", "e5", "Nf3"] {
+            assert!(game.make_move_san(san.to_string()));
+        }
+        let fen_after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(game.request_takeback("bob"), Ok(()));
+        assert_eq!(game.accept_takeback("alice"), Ok(2));
+        assert_eq!(game.get_board().get_fen(), fen_after_e4);
+        assert_eq!(game.get_board().get_moves_history(), "1. e4 ");
+    }
+
+    #[test]
+    fn takeback_request_is_cancelled_by_a_move_or_declined() {
+        let mut game = ongoing_game();
+        assert!(game.make_move_san("e4".to_string()));
+        assert_eq!(game.request_takeback("alice"), Ok(()));
+        assert_eq!(game.request_takeback("bob"), Err("Takeback already requested".to_string()));
+        assert!(game.make_move_san("e5".to_string()));
+        assert!(game.get_takeback_request().is_none());
+        assert!(game.accept_takeback("bob").is_err());
+
+        assert_eq!(game.request_takeback("bob"), Ok(()));
+        assert_eq!(game.decline_takeback("alice"), Ok(()));
+        assert!(game.get_takeback_request().is_none());
+        assert_eq!(game.get_board().get_ply_count(), 2);
+    }
+
+    #[test]
+    fn rated_game_does_not_allow_takebacks() {
+        let mut game = ongoing_game();
+        game.set_rated(true);
+        assert!(game.make_move_san("e4".to_string()));
+        let error = Err("Takebacks are only allowed in casual games".to_string());
+        assert_eq!(game.request_takeback("alice"), error);
+        assert!(game.get_takeback_request().is_none());
+        assert_eq!(game.accept_takeback("bob").map(|_| ()), error);
+        assert_eq!(game.get_board().get_ply_count(), 1);
+    }
+
+    #[test]
+    fn correspondence_deadline_starts_with_the_game_and_resets_after_moves() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_days_per_move(3);
+        assert!(game.get_move_deadline().is_none());
+
+        game.set_user(None, Some("bob".to_string()));
+        let first_deadline = game.get_move_deadline().unwrap();
+        let three_days = Duration::from_secs(3 * 24 * 60 * 60);
+        assert!(first_deadline <= SystemTime::now() + three_days);
+
+        assert!(game.make_move_san("e4".to_string()));
+        assert!(game.get_move_deadline().unwrap() >= first_deadline);
+        assert!(!game.is_out_of_time());
+    }
+
+    #[test]
+    fn missed_correspondence_deadline_loses_on_time() {
+        let mut game = ongoing_game();
+        game.set_days_per_move(1);
+        assert!(game.make_move_san("e4".to_string()));
+        let deadline = game.get_move_deadline().unwrap();
+
+        assert!(!game.flag_if_past_deadline(deadline - Duration::from_secs(1)));
+        assert!(game.flag_if_past_deadline(deadline));
+        assert_eq!(game.get_game_end_condition(), GameEndCondition::WhiteWonOnTime);
+        assert!(game.get_move_deadline().is_none());
+    }
+
+    #[test]
+    fn private_games_and_challenges_are_joined_only_by_invited_users() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        let join_token = game.create_join_token();
+        assert!(game.is_private());
+        assert_eq!(game.can_join("bob", None), Err("Wrong join token".to_string()));
+        assert!(game.can_join("bob", Some("guess")).is_err());
+        assert_eq!(game.can_join("bob", Some(&join_token)), Ok(()));
+        assert!(game.can_join("alice", Some(&join_token)).is_err());
+
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_challenged_user_id(Some("bob".to_string()));
+        assert!(game.can_join("carol", None).is_err());
+        assert!(game.decline_challenge("carol").is_err());
+        assert_eq!(game.decline_challenge("bob"), Ok(()));
+        assert_eq!(game.get_game_status(), GameStatus::Aborted);
+        assert!(game.can_join("bob", None).is_err());
+    }
+
+    #[test]
+    fn rating_category_comes_from_the_time_control() {
+        let mut game = ongoing_game();
+        assert!(game.get_rating_category().is_none());
+        game.set_time_control(TimeControl::new_from_string("3+2").unwrap());
+        assert_eq!(game.get_rating_category(), Some(RatingCategory::Blitz));
+
+        let mut game = ongoing_game();
+        game.set_days_per_move(2);
+        assert_eq!(game.get_rating_category(), Some(RatingCategory::Correspondence));
+    }
+
+    #[test]
+    fn game_can_be_aborted_until_both_sides_have_moved() {
+        let mut game = ongoing_game();
+        assert!(game.make_move_san("e4".to_string()));
+        assert!(game.can_abort());
+        assert!(game.make_move_san("e5".to_string()));
+        assert!(game.abort("bob").is_err());
+
+        let mut game = ongoing_game();
+        assert!(game.abort("carol").is_err());
+        assert_eq!(game.abort("bob"), Ok(GameEndCondition::None));
+        assert_eq!(game.get_game_status(), GameStatus::Aborted);
+    }
+
+    #[test]
+    fn game_is_aborted_when_the_first_move_is_not_made_in_time() {
+        let window = Duration::from_secs(30);
+        let mut game = ongoing_game();
+        let now = Instant::now();
+        assert!(!game.abort_if_first_move_missed(now, window));
+        assert!(game.abort_if_first_move_missed(now + window, window));
+        assert_eq!(game.get_game_status(), GameStatus::Aborted);
+
+        // after both first moves the window no longer applies
+        let mut game = ongoing_game();
+        assert!(game.make_move_san("e4".to_string()));
+        assert!(game.make_move_san("e5".to_string()));
+        assert!(!game.abort_if_first_move_missed(Instant::now() + window, window));
+    }
+
+    #[test]
+    fn correspondence_game_is_not_aborted_after_the_first_move_window() {
+        let window = Duration::from_secs(30);
+        let mut game = ongoing_game();
+        game.set_days_per_move(3);
+        assert!(!game.abort_if_first_move_missed(Instant::now() + window, window));
+        assert_eq!(game.get_game_status(), GameStatus::Ongoing);
+    }
+}
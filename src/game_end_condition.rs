@@ -4,7 +4,7 @@ use tokio_postgres::types::{ToSql, FromSql, Type, IsNull, to_sql_checked};
 use tokio_postgres::types::private::BytesMut;
 use crate::game_status::GameStatus;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GameEndCondition {
     None,
     WhiteCheckmatedBlack,
@@ -15,6 +15,11 @@ pub enum GameEndCondition {
     BlackWonOnTime,
     Draw,
     Stalemate,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
 }
 
 impl GameEndCondition {
@@ -29,6 +34,40 @@ impl GameEndCondition {
             GameEndCondition::BlackWonOnTime => "BlackWonOnTime".to_string(),
             GameEndCondition::Draw => "Draw".to_string(),
             GameEndCondition::Stalemate => "Stalemate".to_string(),
+            GameEndCondition::ThreefoldRepetition => "ThreefoldRepetition".to_string(),
+            GameEndCondition::FivefoldRepetition => "FivefoldRepetition".to_string(),
+            GameEndCondition::FiftyMoveRule => "FiftyMoveRule".to_string(),
+            GameEndCondition::SeventyFiveMoveRule => "SeventyFiveMoveRule".to_string(),
+            GameEndCondition::InsufficientMaterial => "InsufficientMaterial".to_string(),
+        }
+    }
+
+    // Points of White, None while the game has no result.
+    pub fn get_white_score(&self) -> Option<f64> {
+        match self.to_pgn_result().as_str() {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None,
+        }
+    }
+
+    pub fn to_pgn_result(&self) -> String {
+        match self {
+            GameEndCondition::WhiteCheckmatedBlack
+            | GameEndCondition::BlackResigned
+            | GameEndCondition::WhiteWonOnTime => "1-0".to_string(),
+            GameEndCondition::BlackCheckmatedWhite
+            | GameEndCondition::WhiteResigned
+            | GameEndCondition::BlackWonOnTime => "0-1".to_string(),
+            GameEndCondition::Draw
+            | GameEndCondition::Stalemate
+            | GameEndCondition::ThreefoldRepetition
+            | GameEndCondition::FivefoldRepetition
+            | GameEndCondition::FiftyMoveRule
+            | GameEndCondition::SeventyFiveMoveRule
+            | GameEndCondition::InsufficientMaterial => "1/2-1/2".to_string(),
+            GameEndCondition::None => "*".to_string(),
         }
     }
 }
@@ -48,6 +87,11 @@ impl ToSql for GameEndCondition {
             GameEndCondition::BlackWonOnTime => "BlackWonOnTime",
             GameEndCondition::Draw => "Draw",
             GameEndCondition::Stalemate => "Stalemate",
+            GameEndCondition::ThreefoldRepetition => "ThreefoldRepetition",
+            GameEndCondition::FivefoldRepetition => "FivefoldRepetition",
+            GameEndCondition::FiftyMoveRule => "FiftyMoveRule",
+            GameEndCondition::SeventyFiveMoveRule => "SeventyFiveMoveRule",
+            GameEndCondition::InsufficientMaterial => "InsufficientMaterial",
         };
         out.extend_from_slice(condition_str.as_bytes());
         Ok(IsNull::No)
@@ -76,6 +120,11 @@ impl<'a> FromSql<'a> for GameEndCondition {
             "BlackWonOnTime" => Ok(GameEndCondition::BlackWonOnTime),
             "Draw" => Ok(GameEndCondition::Draw),
             "Stalemate" => Ok(GameEndCondition::Stalemate),
+            "ThreefoldRepetition" => Ok(GameEndCondition::ThreefoldRepetition),
+            "FivefoldRepetition" => Ok(GameEndCondition::FivefoldRepetition),
+            "FiftyMoveRule" => Ok(GameEndCondition::FiftyMoveRule),
+            "SeventyFiveMoveRule" => Ok(GameEndCondition::SeventyFiveMoveRule),
+            "InsufficientMaterial" => Ok(GameEndCondition::InsufficientMaterial),
             _ => Err("Unknown game status".into()),
         }
     }
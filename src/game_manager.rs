@@ -1,15 +1,27 @@
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 use crate::connection_manager::ConnectionManager;
 use crate::game::Game;
 use crate::game_repository::GameRepository;
+use crate::game_end_condition::GameEndCondition;
 use crate::game_status::GameStatus;
+use crate::chat::{ChatMessage, ChatRoom};
+use crate::auth::TokenSigner;
+use crate::chess_engine::color::{ActiveColor, Color};
+use crate::lobby::{Lobby, Seek};
+use crate::matchmaking::MatchmakingQueue;
 
 pub struct GameManager {
     pub game_repository: GameRepository,
     games: HashMap<Uuid, Box<Game>>,
     pub connection_manager: ConnectionManager,
+    pub token_signer: TokenSigner,
+    pub lobby: Lobby,
+    pub matchmaking_queue: MatchmakingQueue,
+    // chat messages by game, loaded from the database on first use
+    chat_messages: HashMap<Uuid, Vec<ChatMessage>>,
 }
 
 impl GameManager {
@@ -18,9 +30,36 @@ impl GameManager {
             game_repository,
             games: HashMap::new(),
             connection_manager: ConnectionManager::new(),
+            token_signer: TokenSigner::new_from_env(),
+            lobby: Lobby::new(),
+            matchmaking_queue: MatchmakingQueue::new(),
+            chat_messages: HashMap::new(),
         }
     }
 
+    async fn load_chat_messages(&mut self, game_id: &Uuid) -> &mut Vec<ChatMessage> {
+        if !self.chat_messages.contains_key(game_id) {
+            let chat_messages = self.game_repository.get_chat_messages_by_game_id(*game_id).await.unwrap_or_default();
+            self.chat_messages.insert(*game_id, chat_messages);
+        }
+        self.chat_messages.get_mut(game_id).unwrap()
+    }
+
+    pub async fn get_chat_history(&mut self, game_id: &Uuid, room: &ChatRoom) -> Vec<ChatMessage> {
+        self.load_chat_messages(game_id).await.iter()
+            .filter(|chat_message| &chat_message.room == room)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn add_chat_message(&mut self, chat_message: ChatMessage) -> Result<(), String> {
+        // loaded before storing, so the new message is not read back twice
+        self.load_chat_messages(&chat_message.game_id).await;
+        let result = self.game_repository.add_chat_message(&chat_message).await;
+        self.load_chat_messages(&chat_message.game_id).await.push(chat_message);
+        result
+    }
+
     pub async fn add_game_to_games(&mut self, mut game: Game) -> Result<(Uuid, i32), String> {
         let result = self.game_repository.add_game_to_games(&mut game).await;
         match result {
@@ -35,10 +74,40 @@ impl GameManager {
 
     }
 
+    // Seek of a game awaiting an opponent, with the creator's name and rating.
+    pub async fn create_seek(&self, game: &Game) -> Seek {
+        let user_id = game.get_user1_id().unwrap_or_default();
+        let user_name = self.game_repository.get_user_name_by_id(&user_id).await.unwrap_or(user_id.clone());
+        let category = game.get_rating_category();
+        let rating = match &category {
+            Some(category) => self.game_repository.get_rating(&user_id, category).await.ok()
+                .map(|rating| rating.rating.round() as i32),
+            None => None,
+        };
+        let color = match game.color_by_user_id.get(&user_id) {
+            Some(ActiveColor::White) => Color::White,
+            Some(ActiveColor::Black) => Color::Black,
+            None => Color::Random,
+        };
+        Seek {
+            game_id: game.get_game_id(),
+            user_id,
+            user_name,
+            rating,
+            time_control: game.get_time_control().map(|time_control| time_control.to_string()),
+            days_per_move: game.get_days_per_move(),
+            category,
+            rated: game.is_rated(),
+            color,
+            challenged_user_id: game.get_challenged_user_id(),
+            created_at_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as u64),
+        }
+    }
+
     pub fn get_awaiting_games(&self) -> Vec<Uuid> {
         let ids: Vec<Uuid> = self.games.clone().iter()
             .filter_map(|(uuid, game)| {
-                if matches!(game.get_game_status(), GameStatus::AwaitingOpponent) {
+                if matches!(game.get_game_status(), GameStatus::AwaitingOpponent) && !game.is_private() {
                     Some(*uuid)
                 } else {
                     None
@@ -48,6 +117,100 @@ impl GameManager {
         ids
     }
 
+    pub fn get_games_out_of_time(&self, now: Instant) -> Vec<Uuid> {
+        self.games.iter()
+            .filter(|(_, game)| game.get_game_status() == GameStatus::Ongoing)
+            .filter(|(_, game)| game.get_clock().is_some_and(|clock| clock.get_flagged_color(now).is_some()))
+            .map(|(uuid, _)| *uuid)
+            .collect()
+    }
+
+    pub fn get_games_to_abort(&self, now: Instant, window: Duration) -> Vec<Uuid> {
+        self.games.iter()
+            .filter(|(_, game)| game.is_first_move_missed(now, window))
+            .map(|(uuid, _)| *uuid)
+            .collect()
+    }
+
+    // Applies the result of a finished rated game to the ratings of both players
+    // once, the rating changes are kept on the game.
+    async fn update_ratings(game_repository: &GameRepository, game: &mut Game) -> Result<(), String> {
+        if !game.is_rated() || game.get_game_status() != GameStatus::Finished || game.get_rating_diffs().is_some() {
+            return Ok(());
+        }
+        let (Some(white_id), Some(black_id), Some(category), Some(white_score)) = (
+            game.get_white_id(),
+            game.get_black_id(),
+            game.get_rating_category(),
+            game.get_game_end_condition().get_white_score(),
+        ) else {
+            return Ok(());
+        };
+
+        let white_rating = game_repository.get_rating(&white_id, &category).await?;
+        let black_rating = game_repository.get_rating(&black_id, &category).await?;
+        let new_white_rating = white_rating.update(&black_rating, white_score);
+        let new_black_rating = black_rating.update(&white_rating, 1.0 - white_score);
+        game_repository.update_ratings(&category, &white_id, &new_white_rating, &black_id, &new_black_rating).await?;
+        game.set_rating_diffs(Some((new_white_rating.get_diff(&white_rating), new_black_rating.get_diff(&black_rating))));
+        Ok(())
+    }
+
+    // Ends correspondence games whose side to move has missed the deadline, both
+    // games in memory and games only stored in the database. Returns the result
+    // and the white and black rating changes of rated games.
+    pub async fn adjudicate_missed_deadlines(&mut self, now: SystemTime) -> Vec<(Uuid, GameEndCondition, Option<(i32, i32)>)> {
+        let mut game_ids: Vec<Uuid> = self.games.iter()
+            .filter(|(_, game)| game.get_move_deadline().is_some_and(|move_deadline| move_deadline <= now))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        match self.game_repository.get_game_ids_past_deadline(now).await {
+            Ok(ids) => {
+                for id in ids {
+                    if !game_ids.contains(&id) {
+                        game_ids.push(id);
+                    }
+                }
+            },
+            Err(e) => println!("{}", e),
+        }
+
+        let mut adjudicated = Vec::new();
+        for game_id in game_ids {
+            let result = match self.games.get_mut(&game_id) {
+                Some(game) => match game.flag_if_past_deadline(now) {
+                    true => match self.update_game_by_id(&game_id).await {
+                        Ok(_) => {
+                            let game = &self.games[&game_id];
+                            Ok(Some((game.get_game_end_condition(), game.get_rating_diffs())))
+                        },
+                        Err(e) => Err(e),
+                    },
+                    false => Ok(None),
+                },
+                None => match self.game_repository.get_game_by_id(game_id).await {
+                    Ok(mut game) => match game.flag_if_past_deadline(now) {
+                        true => {
+                            if let Err(e) = GameManager::update_ratings(&self.game_repository, &mut game).await {
+                                println!("Could not update ratings of game {}: {}", game_id, e);
+                            }
+                            self.game_repository.update_game_by_id_db(&game).await
+                                .map(|_| Some((game.get_game_end_condition(), game.get_rating_diffs())))
+                        },
+                        false => Ok(None),
+                    },
+                    Err(e) => Err(e),
+                },
+            };
+            match result {
+                Ok(Some((game_end_condition, rating_diffs))) => adjudicated.push((game_id, game_end_condition, rating_diffs)),
+                Ok(None) => {},
+                Err(e) => println!("Could not adjudicate game {}: {}", game_id, e),
+            }
+        }
+        adjudicated
+    }
+
     pub async fn get_game_by_id(&self, game_id: &Uuid) -> Result<&Game, String> {
         match self.games.get(game_id) {
             Some(game) => Ok(game),
@@ -66,6 +229,9 @@ impl GameManager {
         match self.games.get_mut(game_id) {
             Some(game) => {
                 *game = game.clone();
+                if let Err(e) = GameManager::update_ratings(&self.game_repository, game).await {
+                    println!("Could not update ratings of game {}: {}", game_id, e);
+                }
                 match self.game_repository.update_game_by_id_db(game).await {
                     Ok(_) => {
                         let board_id = game.get_board_id().unwrap();
@@ -92,4 +258,29 @@ impl GameManager {
         // }
         // self.game_repository.update_board_by_id(board_id).await
     // }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn chat_history_is_kept_per_room() {
+        let mut game_manager = GameManager::new(GameRepository::new());
+        let game_id = Uuid::new_v4();
+        for (room, user_id, message) in [
+            (ChatRoom::Players, "alice", "good luck"),
+            (ChatRoom::Spectators, "carol", "nice opening"),
+            (ChatRoom::Players, "bob", "you too"),
+        ] {
+            let chat_message = ChatMessage::new(game_id, room, user_id.to_string(), message).unwrap();
+            // there is no database in tests, the message is still kept in memory
+            assert!(game_manager.add_chat_message(chat_message).await.is_err());
+        }
+
+        let players: Vec<String> = game_manager.get_chat_history(&game_id, &ChatRoom::Players).await
+            .into_iter().map(|chat_message| chat_message.message).collect();
+        assert_eq!(players, vec!["good luck", "you too"]);
+        assert_eq!(game_manager.get_chat_history(&game_id, &ChatRoom::Spectators).await.len(), 1);
+        assert!(game_manager.get_chat_history(&Uuid::new_v4(), &ChatRoom::Players).await.is_empty());
+    }
+}
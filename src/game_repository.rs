@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 use tokio_postgres::{Client, NoTls};
+use tokio_postgres::error::SqlState;
 use diesel::{IntoSql, RunQueryDsl};
 use crate::game::Game;
 use crate::game_status::GameStatus;
@@ -11,6 +13,9 @@ use crate::chess_engine::board::Board;
 use crate::chess_engine::piece::PieceEnum;
 use crate::user::User;
 use crate::chess_engine::coordinates::Coordinates;
+use crate::time_control::TimeControl;
+use crate::chat::ChatMessage;
+use crate::rating::{Rating, RatingCategory};
 
 
 pub struct GameRepository {
@@ -74,6 +79,149 @@ impl GameRepository {
         }
     }
 
+    async fn create_users_table(db_client: &Client) {
+        let _ = db_client.execute("
+        CREATE TABLE IF NOT EXISTS users (
+        id SERIAL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        email TEXT,
+        password_hash TEXT
+        );", &[]).await;
+        // tables created before registration existed
+        let _ = db_client.execute("ALTER TABLE users ADD COLUMN IF NOT EXISTS password_hash TEXT;", &[]).await;
+    }
+
+    // Returns the id of the new user.
+    pub async fn register_user(&self, name: &str, email: &str, password_hash: &str) -> Result<String, String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                GameRepository::create_users_table(db_client).await;
+                let result = db_client.query_one("
+                INSERT INTO users (name, email, password_hash) VALUES ($1, $2, $3) RETURNING id",
+                &[&name, &email, &password_hash]).await;
+                match result {
+                    Ok(row) => Ok(row.get::<usize, i32>(0).to_string()),
+                    Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => Err("User name is already taken".to_string()),
+                    Err(_) => Err("Could not add user".to_string()),
+                }
+            }
+        }
+    }
+
+    // Id and password hash of the user with the given name.
+    pub async fn get_user_credentials_by_name(&self, name: &str) -> Result<(String, String), String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                GameRepository::create_users_table(db_client).await;
+                let result = db_client.query_opt("
+                SELECT id, password_hash FROM users WHERE name = $1", &[&name]).await;
+                match result {
+                    Ok(Some(row)) => match row.get::<&str, Option<String>>("password_hash") {
+                        Some(password_hash) => Ok((row.get::<&str, i32>("id").to_string(), password_hash)),
+                        None => Err("User has no password".to_string()),
+                    },
+                    Ok(None) => Err("User not found".to_string()),
+                    Err(_) => Err("Could not get user".to_string()),
+                }
+            }
+        }
+    }
+
+    pub async fn get_user_name_by_id(&self, user_id: &str) -> Result<String, String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                GameRepository::create_users_table(db_client).await;
+                let user_id: i32 = user_id.parse().map_err(|_| format!("Unknown user {}", user_id))?;
+                let result = db_client.query_opt("SELECT name FROM users WHERE id = $1", &[&user_id]).await;
+                match result {
+                    Ok(Some(row)) => Ok(row.get("name")),
+                    Ok(None) => Err("User not found".to_string()),
+                    Err(_) => Err("Could not get user".to_string()),
+                }
+            }
+        }
+    }
+
+    async fn create_ratings_table(db_client: &Client) {
+        GameRepository::create_users_table(db_client).await;
+        let _ = db_client.execute("
+        CREATE TABLE IF NOT EXISTS ratings (
+        user_id INT NOT NULL,
+        category VARCHAR NOT NULL,
+        rating DOUBLE PRECISION NOT NULL,
+        deviation DOUBLE PRECISION NOT NULL,
+        volatility DOUBLE PRECISION NOT NULL,
+        games INT NOT NULL DEFAULT 0,
+        PRIMARY KEY (user_id, category),
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+        );", &[]).await;
+    }
+
+    // Rating of the user in the category, the default rating before the first rated game.
+    pub async fn get_rating(&self, user_id: &str, category: &RatingCategory) -> Result<Rating, String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                GameRepository::create_ratings_table(db_client).await;
+                let user_id: i32 = user_id.parse().map_err(|_| format!("Unknown user {}", user_id))?;
+                let result = db_client.query_opt("
+                SELECT rating, deviation, volatility FROM ratings WHERE user_id = $1 AND category = $2",
+                &[&user_id, &category.as_str()]).await;
+                match result {
+                    Ok(Some(row)) => Ok(Rating {
+                        rating: row.get("rating"),
+                        deviation: row.get("deviation"),
+                        volatility: row.get("volatility"),
+                    }),
+                    Ok(None) => Ok(Rating::new()),
+                    Err(_) => Err("Could not get rating".to_string()),
+                }
+            }
+        }
+    }
+
+    // Stores the ratings of both players of a game in one statement.
+    pub async fn update_ratings(
+        &self,
+        category: &RatingCategory,
+        white_id: &str,
+        white_rating: &Rating,
+        black_id: &str,
+        black_rating: &Rating,
+    ) -> Result<(), String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                GameRepository::create_ratings_table(db_client).await;
+                let white_id: i32 = white_id.parse().map_err(|_| format!("Unknown user {}", white_id))?;
+                let black_id: i32 = black_id.parse().map_err(|_| format!("Unknown user {}", black_id))?;
+                let result = db_client.execute("
+                INSERT INTO ratings (user_id, category, rating, deviation, volatility, games) VALUES
+                ($1, $2, $3, $4, $5, 1), ($6, $2, $7, $8, $9, 1)
+                ON CONFLICT (user_id, category) DO UPDATE SET rating = EXCLUDED.rating,
+                deviation = EXCLUDED.deviation, volatility = EXCLUDED.volatility, games = ratings.games + 1",
+                &[
+                    &white_id,
+                    &category.as_str(),
+                    &white_rating.rating,
+                    &white_rating.deviation,
+                    &white_rating.volatility,
+                    &black_id,
+                    &black_rating.rating,
+                    &black_rating.deviation,
+                    &black_rating.volatility,
+                ]).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("Could not update ratings".to_string()),
+                }
+            }
+        }
+    }
+
     pub async fn add_users_batch_to_users(&self, users: Vec<User>) -> Result<(), String> {
         match self.db_client {
             Some(ref db_client) => {
@@ -134,15 +282,27 @@ impl GameRepository {
                 black_id TEXT,
                 status VARCHAR NOT NULL,
                 game_end_condition VARCHAR NOT NULL,
+                time_control TEXT,
+                days_per_move INT,
+                move_deadline TIMESTAMPTZ,
+                rated BOOLEAN NOT NULL DEFAULT FALSE,
+                white_rating_diff INT,
+                black_rating_diff INT,
                 FOREIGN KEY (board_id) REFERENCES boards (id) ON DELETE CASCADE
                 );", &[]).await;
+                // tables created before time controls existed
+                let _ = db_client.execute("ALTER TABLE games ADD COLUMN IF NOT EXISTS time_control TEXT, \
+                ADD COLUMN IF NOT EXISTS days_per_move INT, ADD COLUMN IF NOT EXISTS move_deadline TIMESTAMPTZ, \
+                ADD COLUMN IF NOT EXISTS rated BOOLEAN NOT NULL DEFAULT FALSE, \
+                ADD COLUMN IF NOT EXISTS white_rating_diff INT, ADD COLUMN IF NOT EXISTS black_rating_diff INT;", &[]).await;
 
                 let board = game.get_board_mut();
                 match self.add_board_to_boards(board).await {
                     Ok(board_id) => {
                         let result = db_client.query_one("
                         INSERT INTO games (id, board_id, user1_id, user2_id, white_id,
-                        black_id, status, game_end_condition) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+                        black_id, status, game_end_condition, time_control, days_per_move, move_deadline, rated)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id",
                         &[
                             &game.get_game_id(),
                             &board_id,
@@ -152,6 +312,10 @@ impl GameRepository {
                             &game.get_black_id(),
                             &game.get_game_status(),
                             &game.get_game_end_condition(),
+                            &game.get_time_control().map(|time_control| time_control.to_string()),
+                            &game.get_days_per_move().map(|days| days as i32),
+                            &game.get_move_deadline(),
+                            &game.is_rated(),
                         ]).await;
 
                         match result {
@@ -175,7 +339,8 @@ impl GameRepository {
             None => Err("Could not connect to the database".to_string()),
             Some(db_client) => {
                 let result = db_client.query_one("\
-                SELECT id, board_id, user1_id, user2_id, white_id, black_id, status, game_end_condition
+                SELECT id, board_id, user1_id, user2_id, white_id, black_id, status, game_end_condition, time_control,
+                days_per_move, move_deadline, rated, white_rating_diff, black_rating_diff
                 FROM games WHERE id = $1", &[&id]).await;
 
                 match result {
@@ -184,7 +349,7 @@ impl GameRepository {
                         let board = self.get_board_by_id(board_id).await;
                         match board {
                             Ok(board) => {
-                                let game = Game::create_game_from_db(
+                                let mut game = Game::create_game_from_db(
                                     row.get("id"),
                                     board_id,
                                     row.get("user1_id"),
@@ -192,9 +357,22 @@ impl GameRepository {
                                     row.get("white_id"),
                                     row.get("black_id"),
                                     row.get("status"),
-                                    row.get("game_end__condition"),
+                                    row.get("game_end_condition"),
                                     board,
                                 );
+                                let time_control: Option<String> = row.get("time_control");
+                                if let Some(time_control) = time_control {
+                                    game.set_time_control(TimeControl::new_from_string(&time_control)?);
+                                }
+                                let days_per_move: Option<i32> = row.get("days_per_move");
+                                if let Some(days_per_move) = days_per_move {
+                                    game.set_days_per_move(days_per_move as u32);
+                                    game.set_move_deadline(row.get("move_deadline"));
+                                }
+                                game.set_rated(row.get("rated"));
+                                let white_rating_diff: Option<i32> = row.get("white_rating_diff");
+                                let black_rating_diff: Option<i32> = row.get("black_rating_diff");
+                                game.set_rating_diffs(white_rating_diff.zip(black_rating_diff));
                                 Ok(game)
                             },
                             _ => Err("Could not get the board".to_string())
@@ -206,28 +384,36 @@ impl GameRepository {
         }
     }
 
+    async fn create_boards_table(db_client: &Client) {
+        let _ = db_client.execute("
+        CREATE TABLE IF NOT EXISTS boards (
+        id SERIAL PRIMARY KEY,
+        fen TEXT NOT NULL,
+        initial_fen TEXT,
+        active_color CHAR(1) NOT NULL,
+        castle_options TEXT NOT NULL,
+        en_passant_square TEXT,
+        half_move_clock INT,
+        full_move_number INT,
+        number_of_columns INT NOT NULL,
+        number_of_rows INT NOT NULL,
+        columns TEXT NOT NULL,
+        rows TEXT NOT NULL,
+        moves_count INT NOT NULL,
+        moves_history TEXT
+        );", &[]).await;
+        // tables created before set up positions were kept, those boards started
+        // from the initial position
+        let _ = db_client.execute("ALTER TABLE boards ADD COLUMN IF NOT EXISTS initial_fen TEXT;", &[]).await;
+    }
+
     pub async fn add_board_to_boards(&self, board: &mut Board) -> Result<i32, String> {
         match &self.db_client {
             Some(db_client) => {
-                let e = db_client.execute("
-                CREATE TABLE IF NOT EXISTS boards (
-                id SERIAL PRIMARY KEY,
-                fen TEXT NOT NULL,
-                active_color CHAR(1) NOT NULL,
-                castle_options TEXT NOT NULL,
-                en_passant_square TEXT,
-                half_move_clock INT,
-                full_move_number INT,
-                number_of_columns INT NOT NULL,
-                number_of_rows INT NOT NULL,
-                columns TEXT NOT NULL,
-                rows TEXT NOT NULL,
-                moves_count INT NOT NULL,
-                moves_history TEXT
-                );"
-                ,&[]).await;
+                GameRepository::create_boards_table(db_client).await;
 
                 let fen =               board.get_fen();
+                let initial_fen =       board.get_initial_fen();
                 let active_color =      board.get_active_color_string();
                 let castle_options =    board.get_castle_options();
                 let en_passant_square = board.get_en_passant_square();
@@ -242,7 +428,7 @@ impl GameRepository {
 
                 let query = "
                 INSERT INTO boards (fen, active_color, castle_options, en_passant_square, half_move_clock, full_move_number,
-                number_of_columns, number_of_rows, columns, rows, moves_count, moves_history) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                number_of_columns, number_of_rows, columns, rows, moves_count, moves_history, initial_fen) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
                 RETURNING id";
 
                 let result = db_client.query_one(query,
@@ -259,6 +445,7 @@ impl GameRepository {
                     &rows,
                     &moves_count,
                     &moves_history,
+                    &initial_fen,
                 ]).await;
                 match result {
                     Ok(row) => {
@@ -328,8 +515,9 @@ impl GameRepository {
         match &self.db_client {
             None => Err("Could not connect to the database".to_string()),
             Some(db_client) => {
+                GameRepository::create_boards_table(db_client).await;
                 let result = db_client.query_one("\
-                SELECT id, fen, active_color, castle_options, en_passant_square,
+                SELECT id, fen, initial_fen, active_color, castle_options, en_passant_square,
                 half_move_clock, full_move_number, number_of_columns, number_of_rows, columns, rows,
                 moves_count, moves_history
                 FROM boards WHERE id = $1", &[&id]).await;
@@ -344,10 +532,10 @@ impl GameRepository {
 
                         match pieces {
                             Err(e) => Err(e),
-                            Ok(pieces) => Ok(
-                                Board::new_from_db(
+                            Ok(pieces) => Board::new_from_db(
                                     row.get("id"),
                                     row.get("fen"),
+                                    row.get("initial_fen"),
                                     pieces,
                                     active_color,
                                     row.get("castle_options"),
@@ -360,7 +548,7 @@ impl GameRepository {
                                     row.get("rows"),
                                     row.get("moves_count"),
                                     row.get("moves_history"),
-                                )),
+                                ),
                         }
                     }
                 }
@@ -452,7 +640,8 @@ impl GameRepository {
             Some(db_client) => {
                 let game_id = game.get_game_id().to_string();
                 let row_updated = db_client.execute("\
-                UPDATE games SET user1_id = $1, user2_id = $2, white_id = $3, black_id = $4, status = $5, game_end_condition = $6 where id = $7
+                UPDATE games SET user1_id = $1, user2_id = $2, white_id = $3, black_id = $4, status = $5, game_end_condition = $6,
+                move_deadline = $7, white_rating_diff = $8, black_rating_diff = $9 where id = $10
                 ", &[
                     &game.get_user1_id(),
                     &game.get_user2_id(),
@@ -460,6 +649,9 @@ impl GameRepository {
                     &game.get_black_id(),
                     &game.get_game_status(),
                     &game.get_game_end_condition(),
+                    &game.get_move_deadline(),
+                    &game.get_rating_diffs().map(|(white_rating_diff, _)| white_rating_diff),
+                    &game.get_rating_diffs().map(|(_, black_rating_diff)| black_rating_diff),
                     &game.get_game_id(),
                 ]).await;
                 match row_updated {
@@ -470,6 +662,80 @@ impl GameRepository {
         }
     }
 
+    pub async fn get_game_ids_past_deadline(&self, now: SystemTime) -> Result<Vec<Uuid>, String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                let result = db_client.query("\
+                SELECT id FROM games WHERE status = $1 AND move_deadline <= $2
+                ", &[&GameStatus::Ongoing, &now]).await;
+                match result {
+                    Ok(rows) => Ok(rows.iter().map(|row| row.get("id")).collect()),
+                    Err(_) => Err("Could not get games past deadline".to_string()),
+                }
+            }
+        }
+    }
+
+    pub async fn add_chat_message(&self, chat_message: &ChatMessage) -> Result<(), String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                let _ = db_client.execute("
+                CREATE TABLE IF NOT EXISTS chat_messages (
+                id SERIAL PRIMARY KEY,
+                game_id UUID NOT NULL,
+                room VARCHAR NOT NULL,
+                user_id TEXT NOT NULL,
+                message TEXT NOT NULL,
+                sent_at TIMESTAMPTZ NOT NULL,
+                FOREIGN KEY (game_id) REFERENCES games (id) ON DELETE CASCADE
+                );", &[]).await;
+
+                let sent_at = UNIX_EPOCH + Duration::from_millis(chat_message.sent_at_ms);
+                let result = db_client.execute("
+                INSERT INTO chat_messages (game_id, room, user_id, message, sent_at) VALUES
+                ($1, $2, $3, $4, $5)", &[
+                    &chat_message.game_id,
+                    &chat_message.room,
+                    &chat_message.user_id,
+                    &chat_message.message,
+                    &sent_at,
+                ]).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("Could not add chat message".to_string()),
+                }
+            }
+        }
+    }
+
+    // Chat messages of both rooms of a game, oldest first.
+    pub async fn get_chat_messages_by_game_id(&self, game_id: Uuid) -> Result<Vec<ChatMessage>, String> {
+        match &self.db_client {
+            None => Err("Could not connect to the database".to_string()),
+            Some(db_client) => {
+                let result = db_client.query("\
+                SELECT game_id, room, user_id, message, sent_at FROM chat_messages
+                WHERE game_id = $1 ORDER BY sent_at, id
+                ", &[&game_id]).await;
+                match result {
+                    Ok(rows) => Ok(rows.iter().map(|row| {
+                        let sent_at: SystemTime = row.get("sent_at");
+                        ChatMessage {
+                            game_id: row.get("game_id"),
+                            room: row.get("room"),
+                            user_id: row.get("user_id"),
+                            message: row.get("message"),
+                            sent_at_ms: sent_at.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as u64),
+                        }
+                    }).collect()),
+                    Err(_) => Err("Could not get chat messages".to_string()),
+                }
+            }
+        }
+    }
+
     pub fn add_game(
         &mut self,
         game: Game,
@@ -3,12 +3,14 @@ use serde::{Deserialize, Serialize};
 use tokio_postgres::types::{ToSql, FromSql, Type, IsNull, to_sql_checked};
 use tokio_postgres::types::private::BytesMut;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GameStatus {
     AwaitingOpponent,
     Ongoing,
     Finished,
     Aborted,
+    // imported without a result, kept as a record that cannot be played on
+    Archived,
 }
 
 impl GameStatus {
@@ -18,6 +20,7 @@ impl GameStatus {
             GameStatus::Ongoing => "Ongoing".to_string(),
             GameStatus::Finished => "Finished".to_string(),
             GameStatus::Aborted => "Aborted".to_string(),
+            GameStatus::Archived => "Archived".to_string(),
         }
     }
 }
@@ -32,6 +35,7 @@ impl ToSql for GameStatus {
             GameStatus::Ongoing => "Ongoing",
             GameStatus::Finished => "Finished",
             GameStatus::Aborted => "Aborted",
+            GameStatus::Archived => "Archived",
         };
         out.extend_from_slice(status_str.as_bytes());
         Ok(IsNull::No)
@@ -55,6 +59,7 @@ impl<'a> FromSql<'a> for GameStatus {
             "Ongoing" => Ok(GameStatus::Ongoing),
             "Finished" => Ok(GameStatus::Finished),
             "Aborted" => Ok(GameStatus::Aborted),
+            "Archived" => Ok(GameStatus::Archived),
             _ => Err("Unknown game status".into()),
         }
     }
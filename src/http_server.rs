@@ -3,8 +3,10 @@ use axum::{
     response::Response as AxumResponse,
     debug_handler,
 };
-use axum::extract::{Json, State};
+use axum::extract::{Json, Path, Query, State};
+use axum::http::HeaderMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::sync::{Mutex, RwLock};
 use tokio_postgres::types::ToSql;
 use uuid::Uuid;
@@ -12,10 +14,15 @@ use crate::connection_manager::ConnectionManager;
 use crate::request::{
     RequestEnum,
     GetGamesRequest,
+    GetSeeksRequest,
     CreateGameRequest,
     JoinGameRequest,
+    DeclineChallengeRequest,
     AuthorizeWebsocketConnectionRequest,
     MakeMoveRequest,
+    ImportPgnRequest,
+    RegisterRequest,
+    LoginRequest,
 };
 use crate::response::Response;
 
@@ -24,6 +31,9 @@ use crate::game_manager::GameManager;
 use crate::game::Game;
 use crate::event_service::EventService;
 use crate::server::SharedState;
+use crate::pgn::{game_to_pgn, parse_pgn, pgn_to_game};
+use crate::time_control::TimeControl;
+use crate::auth::{hash_password, validate_password, validate_user_name, verify_password};
 
 pub async fn get_games_from_dict(
     State(game_manager): State<Arc<RwLock<GameManager>>>,
@@ -34,15 +44,136 @@ pub async fn get_games_from_dict(
     Response::GetGamesResponse {game_ids: ids}.into_response()
 }
 
+pub async fn get_seeks(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    Query(request): Query<GetSeeksRequest>,
+) -> AxumResponse {
+    println!("Get seeks request");
+    let (seeks, total) = game_manager.read().await.lobby.get_seeks(&request);
+
+    Response::GetSeeksResponse { seeks, total }.into_response()
+}
+
+
+const MAX_DAYS_PER_MOVE: u32 = 30;
+
+pub async fn register(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    Json(request): Json<RegisterRequest>,
+) -> AxumResponse {
+    println!("Register request");
+    let RegisterRequest { name, email, password } = request;
+    if let Err(message) = validate_user_name(&name).and_then(|_| validate_password(&password)) {
+        return Response::RequestFailedResponse { message }.into_response();
+    }
+
+    // hashing is slow on purpose, keep it off the async workers
+    let password_hash = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+        Ok(Ok(password_hash)) => password_hash,
+        _ => return Response::RequestFailedResponse {
+            message: "Could not hash password".to_string(),
+        }.into_response(),
+    };
+
+    let result = game_manager.read().await.game_repository
+        .register_user(&name, &email.unwrap_or_default(), &password_hash).await;
+    match result {
+        Ok(user_id) => Response::RegisterResponse {
+            user_id,
+            message: "User registered successfully".to_string(),
+        }.into_response(),
+        Err(message) => Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    }
+}
+
+pub async fn login(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    Json(request): Json<LoginRequest>,
+) -> AxumResponse {
+    println!("Login request");
+    let LoginRequest { name, password } = request;
+    let credentials = game_manager.read().await.game_repository.get_user_credentials_by_name(&name).await;
+    let (user_id, password_hash) = match credentials {
+        Ok(credentials) => credentials,
+        Err(_) => return Response::RequestFailedResponse {
+            message: "Wrong user name or password".to_string(),
+        }.into_response(),
+    };
+
+    let password_is_valid = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash)).await;
+    if !matches!(password_is_valid, Ok(true)) {
+        return Response::RequestFailedResponse {
+            message: "Wrong user name or password".to_string(),
+        }.into_response();
+    }
+
+    let token = game_manager.read().await.token_signer.issue_token(&user_id, SystemTime::now());
+    Response::LoginResponse {
+        user_id,
+        token,
+        message: "Logged in successfully".to_string(),
+    }.into_response()
+}
 
 pub async fn create_game(
     State(mut game_manager): State<Arc<RwLock<GameManager>>>,
+    headers: HeaderMap,
     Json(request): Json<CreateGameRequest>,
 ) -> AxumResponse {
-    let CreateGameRequest { user_id, color } = request;
+    let CreateGameRequest { color, time_control, days_per_move, rated, private, opponent_id } = request;
     println!("Create game request");
+    let user_id = match game_manager.read().await.token_signer.authenticate(&headers) {
+        Ok(user_id) => user_id,
+        Err(message) => return Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    };
+    let mut game = Game::new(user_id.clone(), color);
+    match (time_control, days_per_move) {
+        (Some(_), Some(_)) => return Response::RequestFailedResponse {
+            message: "A game has either a time control or days per move".to_string(),
+        }.into_response(),
+        (Some(time_control), None) => match TimeControl::new_from_string(&time_control) {
+            Ok(time_control) => game.set_time_control(time_control),
+            Err(message) => return Response::RequestFailedResponse {
+                message,
+            }.into_response(),
+        },
+        (None, Some(days_per_move)) if (1..=MAX_DAYS_PER_MOVE).contains(&days_per_move) => {
+            game.set_days_per_move(days_per_move)
+        },
+        (None, Some(days_per_move)) => return Response::RequestFailedResponse {
+            message: format!("Days per move must be between 1 and {}, got {}", MAX_DAYS_PER_MOVE, days_per_move),
+        }.into_response(),
+        (None, None) => {},
+    }
+    if rated && game.get_rating_category().is_none() {
+        return Response::RequestFailedResponse {
+            message: "Rated games need a time control or days per move".to_string(),
+        }.into_response();
+    }
+    game.set_rated(rated);
+    let join_token = match private {
+        true => Some(game.create_join_token()),
+        false => None,
+    };
+    if let Some(opponent_id) = &opponent_id {
+        if opponent_id == &user_id {
+            return Response::RequestFailedResponse {
+                message: "Cannot challenge yourself".to_string(),
+            }.into_response();
+        }
+        if game_manager.read().await.game_repository.get_user_name_by_id(opponent_id).await.is_err() {
+            return Response::RequestFailedResponse {
+                message: "Unknown opponent".to_string(),
+            }.into_response();
+        }
+    }
+    game.set_challenged_user_id(opponent_id);
+
     let mut game_manager_lock = game_manager.write().await;
-    let game = Game::new(user_id.clone(), color);
     let response = game_manager_lock.add_game_to_games(game.clone()).await;
     match response {
         Ok((game_id, _)) => {
@@ -52,8 +183,17 @@ pub async fn create_game(
                 None,
                 None,
             );
+            // private games are only reached through the join token
+            if game.get_challenged_user_id().is_some() {
+                let seek = game_manager_lock.create_seek(&game).await;
+                game_manager_lock.lobby.add_challenge(seek);
+            } else if !game.is_private() {
+                let seek = game_manager_lock.create_seek(&game).await;
+                game_manager_lock.lobby.add_seek(seek);
+            }
             Response::CreateGameResponse {
                 game_id,
+                join_token,
                 message: "Game created successfully".to_string(),
             }.into_response()
         },
@@ -66,20 +206,17 @@ pub async fn create_game(
 
 pub async fn join_game(
     State(mut game_manager): State<Arc<RwLock<GameManager>>>,
+    headers: HeaderMap,
     Json(request): Json<JoinGameRequest>
 ) -> AxumResponse {
     println!("Join game request");
-    let JoinGameRequest { game_id, user_id } = request;
-
-    // add a new connection for a new user
-    {
-        let _ = game_manager.write().await.connection_manager.add_connection(
-            &game_id,
-            &user_id,
-            None,
-            None,
-        );
-    }
+    let JoinGameRequest { game_id, join_token } = request;
+    let user_id = match game_manager.read().await.token_signer.authenticate(&headers) {
+        Ok(user_id) => user_id,
+        Err(message) => return Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    };
 
     // add user to a game
     {
@@ -95,9 +232,22 @@ pub async fn join_game(
             },
         };
 
-        if game.get_users().0.unwrap() != user_id && game.get_users().1.is_none() {
-            game.set_user(None, Some(user_id.clone()));
+        if let Err(message) = game.can_join(&user_id, join_token.as_deref()) {
+            return Response::RequestFailedResponse {
+                message,
+            }.into_response();
         }
+        game.set_user(None, Some(user_id.clone()));
+        game_manager_lock.lobby.remove_seek(&game_id);
+        game_manager_lock.lobby.remove_challenge(&game_id, "Accepted");
+
+        // add a new connection for a new user
+        let _ = game_manager_lock.connection_manager.add_connection(
+            &game_id,
+            &user_id,
+            None,
+            None,
+        );
     }
 
     // update game in the database
@@ -126,3 +276,118 @@ pub async fn join_game(
     }
 }
 
+// Open challenges the logged in user made or received.
+pub async fn get_challenges(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    headers: HeaderMap,
+) -> AxumResponse {
+    println!("Get challenges request");
+    let game_manager_lock = game_manager.read().await;
+    match game_manager_lock.token_signer.authenticate(&headers) {
+        Ok(user_id) => {
+            let seeks = game_manager_lock.lobby.get_challenges(&user_id);
+            let total = seeks.len();
+            Response::GetSeeksResponse { seeks, total }.into_response()
+        },
+        Err(message) => Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    }
+}
+
+pub async fn decline_challenge(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    headers: HeaderMap,
+    Json(request): Json<DeclineChallengeRequest>,
+) -> AxumResponse {
+    println!("Decline challenge request");
+    let DeclineChallengeRequest { game_id } = request;
+    let mut game_manager_lock = game_manager.write().await;
+    let user_id = match game_manager_lock.token_signer.authenticate(&headers) {
+        Ok(user_id) => user_id,
+        Err(message) => return Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    };
+
+    let result = match game_manager_lock.get_mutable_game_by_id(&game_id).await {
+        Ok(game) => game.decline_challenge(&user_id).map(|_| game.get_users().0.unwrap_or_default()),
+        Err(message) => Err(message),
+    };
+    match result {
+        Ok(challenger_id) => {
+            let _ = game_manager_lock.update_game_by_id(&game_id).await;
+            game_manager_lock.lobby.remove_challenge(&game_id, "Declined");
+            Response::ChallengeResponse {
+                game_id,
+                challenger_id,
+                challenged_user_id: user_id,
+                state: "Declined".to_string(),
+                message: "Challenge declined".to_string(),
+            }.into_response()
+        },
+        Err(message) => Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    }
+}
+
+pub async fn get_game_pgn(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    Path(game_id): Path<Uuid>,
+) -> AxumResponse {
+    println!("Get game pgn request");
+    let game_manager_lock = game_manager.read().await;
+
+    // finished games may no longer be kept in memory, fall back to the database
+    let pgn = match game_manager_lock.get_game_by_id(&game_id).await {
+        Ok(game) => game_to_pgn(game),
+        Err(_) => match game_manager_lock.game_repository.get_game_by_id(game_id).await {
+            Ok(game) => game_to_pgn(&game),
+            Err(message) => return Response::RequestFailedResponse {
+                message,
+            }.into_response(),
+        },
+    };
+
+    Response::GetGamePgnResponse { game_id, pgn }.into_response()
+}
+
+// Imported games are only stored as records, they are not loaded as live games.
+pub async fn import_pgn(
+    State(game_manager): State<Arc<RwLock<GameManager>>>,
+    headers: HeaderMap,
+    Json(request): Json<ImportPgnRequest>,
+) -> AxumResponse {
+    println!("Import pgn request");
+    let ImportPgnRequest { pgn } = request;
+    let game_manager_lock = game_manager.read().await;
+    if let Err(message) = game_manager_lock.token_signer.authenticate(&headers) {
+        return Response::RequestFailedResponse {
+            message,
+        }.into_response();
+    }
+
+    let pgn_games = match parse_pgn(&pgn) {
+        Ok(pgn_games) => pgn_games,
+        Err(message) => return Response::RequestFailedResponse {
+            message,
+        }.into_response(),
+    };
+
+    // games are imported independently, one bad game does not stop the others
+    let mut game_ids: Vec<Uuid> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    for (index, pgn_game) in pgn_games.iter().enumerate() {
+        let result = match pgn_to_game(pgn_game) {
+            Ok(mut game) => game_manager_lock.game_repository.add_game_to_games(&mut game).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok((game_id, _)) => game_ids.push(game_id),
+            Err(e) => errors.push(format!("Game {}: {}", index + 1, e)),
+        }
+    }
+
+    Response::ImportPgnResponse { game_ids, errors }.into_response()
+}
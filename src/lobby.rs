@@ -0,0 +1,202 @@
+// Open seeks and direct challenges of games awaiting an opponent.
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use crate::chess_engine::color::Color;
+use crate::rating::RatingCategory;
+use crate::request::GetSeeksRequest;
+use crate::response::Response;
+
+pub const DEFAULT_SEEKS_PAGE_SIZE: usize = 20;
+pub const MAX_SEEKS_PAGE_SIZE: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seek {
+    pub game_id: Uuid,
+    pub user_id: String,
+    pub user_name: String,
+    // creator's rating in the category, None for untimed games
+    pub rating: Option<i32>,
+    pub time_control: Option<String>,
+    pub days_per_move: Option<u32>,
+    pub category: Option<RatingCategory>,
+    pub rated: bool,
+    // color the creator plays
+    pub color: Color,
+    // set for a direct challenge, which is not listed
+    pub challenged_user_id: Option<String>,
+    // milliseconds since the unix epoch
+    pub created_at_ms: u64,
+}
+
+impl Seek {
+    pub fn matches(&self, filter: &GetSeeksRequest) -> bool {
+        let in_rating_range = match (filter.min_rating, filter.max_rating) {
+            (None, None) => true,
+            (min_rating, max_rating) => self.rating.is_some_and(|rating| {
+                min_rating.is_none_or(|min_rating| rating >= min_rating)
+                    && max_rating.is_none_or(|max_rating| rating <= max_rating)
+            }),
+        };
+        in_rating_range
+            && filter.rated.is_none_or(|rated| rated == self.rated)
+            && filter.category.as_ref().is_none_or(|category| self.category.as_ref() == Some(category))
+            && filter.color.as_ref().is_none_or(|color| color == &self.color)
+    }
+}
+
+// Seeks changed by the HTTP server are announced on the channel, the websocket
+// server forwards them to the lobby connections.
+pub struct Lobby {
+    seeks: HashMap<Uuid, Seek>,
+    challenges: HashMap<Uuid, Seek>,
+    sender: broadcast::Sender<Response>,
+}
+
+impl Lobby {
+    pub fn new() -> Lobby {
+        let (sender, _) = broadcast::channel(100);
+        Lobby { seeks: HashMap::new(), challenges: HashMap::new(), sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Response> {
+        self.sender.subscribe()
+    }
+
+    pub fn add_seek(&mut self, seek: Seek) {
+        self.seeks.insert(seek.game_id, seek.clone());
+        // nobody may be listening yet
+        let _ = self.sender.send(Response::SeekAddedResponse { seek });
+    }
+
+    pub fn remove_seek(&mut self, game_id: &Uuid) {
+        if self.seeks.remove(game_id).is_some() {
+            let _ = self.sender.send(Response::SeekRemovedResponse { game_id: *game_id });
+        }
+    }
+
+    pub fn add_challenge(&mut self, seek: Seek) {
+        let challenged_user_id = seek.challenged_user_id.clone().unwrap_or_default();
+        let response = Response::ChallengeResponse {
+            game_id: seek.game_id,
+            challenger_id: seek.user_id.clone(),
+            challenged_user_id: challenged_user_id.clone(),
+            state: "Offered".to_string(),
+            message: format!("{} challenged {}", seek.user_name, challenged_user_id),
+        };
+        self.challenges.insert(seek.game_id, seek);
+        let _ = self.sender.send(response);
+    }
+
+    // state is "Accepted", "Declined" or "Cancelled"
+    pub fn remove_challenge(&mut self, game_id: &Uuid, state: &str) {
+        if let Some(seek) = self.challenges.remove(game_id) {
+            let challenged_user_id = seek.challenged_user_id.unwrap_or_default();
+            let _ = self.sender.send(Response::ChallengeResponse {
+                game_id: *game_id,
+                message: format!("Challenge of {} to {} {}", seek.user_name, challenged_user_id, state.to_lowercase()),
+                challenger_id: seek.user_id,
+                challenged_user_id,
+                state: state.to_string(),
+            });
+        }
+    }
+
+    // Open challenges the user made or received, oldest first.
+    pub fn get_challenges(&self, user_id: &str) -> Vec<Seek> {
+        let mut challenges: Vec<Seek> = self.challenges.values()
+            .filter(|seek| seek.user_id == user_id || seek.challenged_user_id.as_deref() == Some(user_id))
+            .cloned()
+            .collect();
+        challenges.sort_by_key(|seek| (seek.created_at_ms, seek.game_id));
+        challenges
+    }
+
+    // A page of the matching seeks, oldest first, and the number of matching seeks.
+    pub fn get_seeks(&self, filter: &GetSeeksRequest) -> (Vec<Seek>, usize) {
+        let mut seeks: Vec<&Seek> = self.seeks.values().filter(|seek| seek.matches(filter)).collect();
+        seeks.sort_by_key(|seek| (seek.created_at_ms, seek.game_id));
+        let limit = filter.limit.unwrap_or(DEFAULT_SEEKS_PAGE_SIZE).min(MAX_SEEKS_PAGE_SIZE);
+        let page = seeks.iter().skip(filter.offset).take(limit).map(|seek| (*seek).clone()).collect();
+        (page, seeks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seek(rating: Option<i32>, rated: bool, created_at_ms: u64) -> Seek {
+        Seek {
+            game_id: Uuid::new_v4(),
+            user_id: "1".to_string(),
+            user_name: "alice".to_string(),
+            rating,
+            time_control: rating.map(|_| "5+3".to_string()),
+            days_per_move: None,
+            category: rating.map(|_| RatingCategory::Blitz),
+            rated,
+            color: Color::Random,
+            challenged_user_id: None,
+            created_at_ms,
+        }
+    }
+
+    #[test]
+    fn seeks_are_filtered_and_paginated_oldest_first() {
+        let mut lobby = Lobby::new();
+        lobby.add_seek(seek(Some(1400), true, 3));
+        lobby.add_seek(seek(Some(1600), true, 1));
+        lobby.add_seek(seek(Some(1800), false, 2));
+        lobby.add_seek(seek(None, false, 4));
+
+        let (seeks, total) = lobby.get_seeks(&GetSeeksRequest::default());
+        assert_eq!(total, 4);
+        assert_eq!(seeks.iter().map(|seek| seek.created_at_ms).collect::<Vec<u64>>(), vec![1, 2, 3, 4]);
+
+        let filter = GetSeeksRequest { rated: Some(true), min_rating: Some(1500), ..Default::default() };
+        let (seeks, total) = lobby.get_seeks(&filter);
+        assert_eq!((seeks[0].rating, total), (Some(1600), 1));
+
+        let filter = GetSeeksRequest { category: Some(RatingCategory::Blitz), offset: 1, limit: Some(1), ..Default::default() };
+        let (seeks, total) = lobby.get_seeks(&filter);
+        assert_eq!((seeks[0].created_at_ms, total), (2, 3));
+    }
+
+    #[test]
+    fn seek_changes_are_announced() {
+        let mut lobby = Lobby::new();
+        let mut receiver = lobby.subscribe();
+        let seek = seek(Some(1500), true, 1);
+        let game_id = seek.game_id;
+        lobby.add_seek(seek);
+        lobby.remove_seek(&game_id);
+        lobby.remove_seek(&game_id);
+
+        assert!(matches!(receiver.try_recv(), Ok(Response::SeekAddedResponse { .. })));
+        assert!(matches!(receiver.try_recv(), Ok(Response::SeekRemovedResponse { game_id: id }) if id == game_id));
+        assert!(receiver.try_recv().is_err());
+        assert!(lobby.get_seeks(&GetSeeksRequest::default()).0.is_empty());
+    }
+
+    #[test]
+    fn challenges_are_kept_apart_from_seeks() {
+        let mut lobby = Lobby::new();
+        let mut receiver = lobby.subscribe();
+        let mut challenge = seek(Some(1500), true, 1);
+        challenge.challenged_user_id = Some("2".to_string());
+        let game_id = challenge.game_id;
+        lobby.add_challenge(challenge);
+
+        assert_eq!(lobby.get_seeks(&GetSeeksRequest::default()).1, 0);
+        assert_eq!(lobby.get_challenges("2").len(), 1);
+        assert_eq!(lobby.get_challenges("1").len(), 1);
+        assert!(lobby.get_challenges("3").is_empty());
+
+        lobby.remove_challenge(&game_id, "Declined");
+        assert!(lobby.get_challenges("2").is_empty());
+        assert!(matches!(receiver.try_recv(), Ok(Response::ChallengeResponse { state, .. }) if state == "Offered"));
+        assert!(matches!(receiver.try_recv(), Ok(Response::ChallengeResponse { state, .. }) if state == "Declined"));
+    }
+}
@@ -13,6 +13,13 @@ mod game_manager;
 mod websocket_server_new;
 mod connection_manager;
 mod game_end_condition;
+mod pgn;
+mod time_control;
+mod chat;
+mod auth;
+mod rating;
+mod lobby;
+mod matchmaking;
 
 use std::collections::HashMap;
 use chess_engine::board::Board;
@@ -0,0 +1,160 @@
+// Quick pairing: players wait in a queue per time control until the matchmaker
+// finds an opponent with a close rating.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Instant;
+use crate::time_control::TimeControl;
+
+// rating difference accepted right after joining
+const INITIAL_RATING_WINDOW: i32 = 50;
+const RATING_WINDOW_GROWTH_PER_SECOND: i32 = 10;
+const MAX_RATING_WINDOW: i32 = 500;
+
+#[derive(Clone, Debug)]
+pub struct QueueEntry {
+    pub user_id: String,
+    // websocket connection told about the match
+    pub connection_id: SocketAddr,
+    pub time_control: TimeControl,
+    pub rated: bool,
+    pub rating: i32,
+    pub joined_at: Instant,
+}
+
+impl QueueEntry {
+    // The accepted rating difference widens the longer the player waits.
+    pub fn get_rating_window(&self, now: Instant) -> i32 {
+        let waited_seconds = now.saturating_duration_since(self.joined_at).as_secs() as i32;
+        waited_seconds.saturating_mul(RATING_WINDOW_GROWTH_PER_SECOND)
+            .saturating_add(INITIAL_RATING_WINDOW)
+            .min(MAX_RATING_WINDOW)
+    }
+
+    fn accepts(&self, other: &QueueEntry, now: Instant) -> bool {
+        (self.rating - other.rating).abs() <= self.get_rating_window(now).min(other.get_rating_window(now))
+    }
+}
+
+pub struct MatchmakingQueue {
+    // by time control and rated, oldest entry first
+    entries: HashMap<(String, bool), Vec<QueueEntry>>,
+}
+
+impl MatchmakingQueue {
+    pub fn new() -> MatchmakingQueue {
+        MatchmakingQueue { entries: HashMap::new() }
+    }
+
+    // A user waits in one queue at a time, joining again replaces the old entry.
+    pub fn join(&mut self, entry: QueueEntry) {
+        self.leave(&entry.user_id);
+        let key = (entry.time_control.to_string(), entry.rated);
+        self.entries.entry(key).or_default().push(entry);
+    }
+
+    pub fn leave(&mut self, user_id: &str) -> bool {
+        self.remove_entries(|entry| entry.user_id == user_id)
+    }
+
+    pub fn leave_by_connection(&mut self, connection_id: &SocketAddr) -> bool {
+        self.remove_entries(|entry| &entry.connection_id == connection_id)
+    }
+
+    fn remove_entries<F>(&mut self, predicate: F) -> bool
+    where
+        F: Fn(&QueueEntry) -> bool,
+    {
+        let mut removed = false;
+        for entries in self.entries.values_mut() {
+            let length = entries.len();
+            entries.retain(|entry| !predicate(entry));
+            removed |= entries.len() != length;
+        }
+        self.entries.retain(|_, entries| !entries.is_empty());
+        removed
+    }
+
+    pub fn get_queue_length(&self, time_control: &TimeControl, rated: bool) -> usize {
+        self.entries.get(&(time_control.to_string(), rated)).map_or(0, |entries| entries.len())
+    }
+
+    // Takes the pairs out of the queue. The longest waiting player is paired
+    // first, with the closest rating both players accept.
+    pub fn find_pairs(&mut self, now: Instant) -> Vec<(QueueEntry, QueueEntry)> {
+        let mut pairs = Vec::new();
+        for entries in self.entries.values_mut() {
+            let mut index = 0;
+            while index < entries.len() {
+                let entry = &entries[index];
+                let opponent_index = entries.iter().enumerate()
+                    .skip(index + 1)
+                    .filter(|(_, opponent)| entry.accepts(opponent, now))
+                    .min_by_key(|(_, opponent)| (opponent.rating - entry.rating).abs())
+                    .map(|(opponent_index, _)| opponent_index);
+                match opponent_index {
+                    Some(opponent_index) => {
+                        let opponent = entries.remove(opponent_index);
+                        let entry = entries.remove(index);
+                        pairs.push((entry, opponent));
+                    },
+                    None => index += 1,
+                }
+            }
+        }
+        self.entries.retain(|_, entries| !entries.is_empty());
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn entry(user_id: &str, time_control: &str, rating: i32, joined_at: Instant) -> QueueEntry {
+        QueueEntry {
+            user_id: user_id.to_string(),
+            connection_id: "127.0.0.1:5000".parse().unwrap(),
+            time_control: TimeControl::new_from_string(time_control).unwrap(),
+            rated: true,
+            rating,
+            joined_at,
+        }
+    }
+
+    #[test]
+    fn close_ratings_are_paired_and_the_window_widens_over_time() {
+        let mut queue = MatchmakingQueue::new();
+        let now = Instant::now();
+        queue.join(entry("1", "5+3", 1500, now));
+        queue.join(entry("2", "5+3", 1800, now));
+        queue.join(entry("3", "5+3", 1530, now));
+
+        let pairs = queue.find_pairs(now);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.user_id.as_str(), pairs[0].1.user_id.as_str()), ("1", "3"));
+        assert_eq!(queue.get_queue_length(&TimeControl::new_from_string("5+3").unwrap(), true), 1);
+
+        queue.join(entry("4", "5+3", 1500, now));
+        assert!(queue.find_pairs(now + Duration::from_secs(10)).is_empty());
+        assert_eq!(queue.find_pairs(now + Duration::from_secs(30)).len(), 1);
+    }
+
+    #[test]
+    fn players_of_different_queues_are_not_paired() {
+        let mut queue = MatchmakingQueue::new();
+        let now = Instant::now();
+        queue.join(entry("1", "5+3", 1500, now));
+        queue.join(entry("2", "3+2", 1500, now));
+        let mut casual = entry("3", "5+3", 1500, now);
+        casual.rated = false;
+        queue.join(casual);
+        assert!(queue.find_pairs(now).is_empty());
+
+        // joining again moves the user to the new queue
+        queue.join(entry("3", "3+2", 1500, now));
+        assert_eq!(queue.find_pairs(now).len(), 1);
+        assert!(queue.leave("1"));
+        assert!(!queue.leave("1"));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::chess_engine::board::{Board, INITIAL_FEN};
+use crate::chess_engine::color::Color;
+use crate::game::Game;
+use crate::game_end_condition::GameEndCondition;
+use crate::game_status::GameStatus;
+
+const PGN_LINE_LENGTH: usize = 80;
+
+// Exports a game as PGN with the Seven Tag Roster. Unknown tag values are written
+// as "?" as required by the PGN standard. Games set up from a position also get
+// the SetUp and FEN tags.
+pub fn game_to_pgn(game: &Game) -> String {
+    let result = game.get_game_end_condition().to_pgn_result();
+    let event = match game.is_rated() {
+        true => "Rated game",
+        false => "Casual game",
+    };
+    let mut tags = vec![
+        ("Event", event.to_string()),
+        ("Site", "?".to_string()),
+        ("Date", "????.??.??".to_string()),
+        ("Round", "-".to_string()),
+        ("White", game.get_white_id().unwrap_or("?".to_string())),
+        ("Black", game.get_black_id().unwrap_or("?".to_string())),
+        ("Result", result.clone()),
+    ];
+    let initial_fen = game.get_board().get_initial_fen();
+    if initial_fen != INITIAL_FEN {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", initial_fen));
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(format!("[{} \"{}\"]\n", name, escape_tag_value(&value)).as_str());
+    }
+    pgn.push('\n');
+
+    let movetext = format!("{} {}", game.get_board().get_moves_history().trim(), result);
+    pgn.push_str(wrap_movetext(movetext.trim()).as_str());
+    pgn.push('\n');
+    pgn
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn wrap_movetext(movetext: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for token in movetext.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.moves.is_empty()
+    }
+}
+
+// Parses one or more games. Comments, recursive variations and numeric
+// annotation glyphs are skipped; move legality is not checked here.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game = PgnGame::default();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                // a tag section after movetext without a result starts a new game
+                if !game.moves.is_empty() {
+                    games.push(std::mem::take(&mut game));
+                }
+                game.tags.push(parse_tag(&mut chars)?);
+            },
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err("Unterminated comment".to_string());
+                }
+            },
+            ';' => {
+                let _ = chars.by_ref().any(|c| c == '\n');
+            },
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => { let _ = chars.by_ref().any(|c| c == '}'); },
+                        Some(_) => {},
+                        None => return Err("Unterminated variation".to_string()),
+                    }
+                }
+            },
+            c if c.is_whitespace() => {},
+            c => {
+                let mut token = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "[]{}();".contains(next) {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+
+                match token.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                        game.result = token;
+                        games.push(std::mem::take(&mut game));
+                    },
+                    "0-0" | "0-0-0" => game.moves.push(token),
+                    _ if token.starts_with('$') => {},
+                    _ => {
+                        // move numbers, "12." or "12...", may be glued to the move
+                        let san = token.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches('.');
+                        if !san.is_empty() {
+                            game.moves.push(san.to_string());
+                        }
+                    },
+                }
+            },
+        }
+    }
+
+    if !game.is_empty() {
+        game.result = "*".to_string();
+        games.push(game);
+    }
+
+    match games.is_empty() {
+        true => Err("No games found".to_string()),
+        false => Ok(games),
+    }
+}
+
+fn parse_tag(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<(String, String), String> {
+    let mut name = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+
+    loop {
+        match (chars.next(), in_value) {
+            (Some('"'), false) => in_value = true,
+            (Some('"'), true) => {
+                let _ = chars.by_ref().any(|c| c == ']');
+                return Ok((name.trim().to_string(), value));
+            },
+            (Some('\\'), true) => {
+                if let Some(escaped) = chars.next() {
+                    value.push(escaped);
+                }
+            },
+            (Some(c), true) => value.push(c),
+            (Some(']'), false) => return Err(format!("Tag {} has no value", name.trim())),
+            (Some(c), false) => name.push(c),
+            (None, _) => return Err("Unterminated tag".to_string()),
+        }
+    }
+}
+
+// Replays a parsed game through the board to build a `Game`. Illegal moves are
+// reported with their ply number. Games that end without mate or stalemate on
+// the board take their result from the Result tag, as a resignation or a draw.
+// Imported games are records: the White and Black tags are player names, and a
+// game without a result is archived rather than left to be played on.
+pub fn pgn_to_game(pgn_game: &PgnGame) -> Result<Game, String> {
+    let white_id = pgn_game.get_tag("White").unwrap_or("?").to_string();
+    let black_id = pgn_game.get_tag("Black").unwrap_or("?").to_string();
+    if white_id == black_id {
+        return Err(format!("White and Black tags must name different players, both are \"{}\"", white_id));
+    }
+
+    let mut game = match pgn_game.get_tag("FEN") {
+        Some(fen) => {
+            validate_fen(fen)?;
+            let board = Board::new_from_fen("abcdefgh".to_string(), 8, "12345678".to_string(), 8, fen.to_string());
+            Game::create_game_from_board(white_id, board, Color::White)
+        },
+        None => Game::new(white_id, Color::White),
+    };
+    game.set_user(None, Some(black_id));
+    game.update_game_status_and_end_condition();
+
+    for (index, san) in pgn_game.moves.iter().enumerate() {
+        if !matches!(game.get_game_status(), GameStatus::Ongoing) || !game.make_move_san(san.clone()) {
+            return Err(format!("Illegal move {} at ply {}", san, index + 1));
+        }
+    }
+
+    if matches!(game.get_game_status(), GameStatus::Ongoing) {
+        let game_end_condition = match pgn_game.result.as_str() {
+            "1-0" => Some(GameEndCondition::BlackResigned),
+            "0-1" => Some(GameEndCondition::WhiteResigned),
+            "1/2-1/2" => Some(GameEndCondition::Draw),
+            _ => None,
+        };
+        match game_end_condition {
+            Some(game_end_condition) => game.set_game_status_and_end_condition(GameStatus::Finished, game_end_condition),
+            None => game.set_game_status_and_end_condition(GameStatus::Archived, GameEndCondition::None),
+        }
+    }
+    Ok(game)
+}
+
+fn validate_fen(fen: &str) -> Result<(), String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let valid = fields.len() == 6
+        && fields[0].split('/').count() == 8
+        && fields[0].split('/').all(|rank| rank.chars().map(|c| c.to_digit(10).unwrap_or(1)).sum::<u32>() == 8)
+        && fields[0].chars().all(|c| c == '/' || c.is_ascii_digit() || "pnbrqkPNBRQK".contains(c))
+        && ["w", "b"].contains(&fields[1])
+        && fields[4].parse::<i32>().is_ok()
+        && fields[5].parse::<i32>().is_ok();
+
+    match valid {
+        true => Ok(()),
+        false => Err(format!("Invalid FEN: {}", fen)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::chess_engine::color::Color;
+    use crate::game::Game;
+    use crate::game_end_condition::GameEndCondition;
+    use crate::game_status::GameStatus;
+    use crate::pgn::{game_to_pgn, parse_pgn, pgn_to_game};
+
+    const PLAYERS: &str = "[White \"alice\"]\n[Black \"bob\"]\n";
+
+    #[test]
+    fn exports_seven_tag_roster_and_result() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_user(None, Some("bob".to_string()));
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            assert!(game.make_move_san(san.to_string()));
+        }
+
+        assert_eq!(game_to_pgn(&game), "\
+[Event \"Casual game\"]
+[Site \"?\"]
+[Date \"????.??.??\"]
+[Round \"-\"]
+[White \"alice\"]
+[Black \"bob\"]
+[Result \"0-1\"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+");
+    }
+
+    #[test]
+    fn ongoing_game_has_unknown_result_and_wrapped_movetext() {
+        let mut game = Game::new("alice".to_string(), Color::Black);
+        let moves = "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7 c4 c6 cxb5 axb5";
+        for san in moves.split(' ') {
+            assert!(game.make_move_san(san.to_string()), "could not make move {}", san);
+        }
+
+        let pgn = game_to_pgn(&game);
+        assert!(pgn.contains("[White \"?\"]\n[Black \"alice\"]\n[Result \"*\"]\n"));
+        assert!(pgn.starts_with("[Event \"Casual game\"]\n"));
+        assert!(pgn.trim_end().ends_with("12. cxb5 axb5 *"));
+        assert!(pgn.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn rated_game_is_named_in_event_tag() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_rated(true);
+        assert!(game_to_pgn(&game).starts_with("[Event \"Rated game\"]\n"));
+    }
+
+    #[test]
+    fn parses_multiple_games_with_comments_and_variations() {
+        let games = parse_pgn("\
+[Event \"Club \\\"open\\\"\"]
+[White \"alice\"]
+[Black \"bob\"]
+
+1.e4 {best by test} e5 2. Nf3 (2. f4 exf4 {gambit}) 2... Nc6 $1 3. Bb5 a6!? ; Ruy Lopez
+4. O-O 1/2-1/2
+
+[White \"carol\"]
+[Black \"dave\"]
+
+1. d4 d5 *
+").unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_tag("Event"), Some("Club \"open\""));
+        assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6!?", "O-O"]);
+        assert_eq!(games[0].result, "1/2-1/2");
+        assert_eq!(games[1].get_tag("White"), Some("carol"));
+        assert_eq!(games[1].moves, vec!["d4", "d5"]);
+        assert_eq!(games[1].result, "*");
+    }
+
+    #[test]
+    fn rejects_malformed_pgn() {
+        assert!(parse_pgn("").is_err());
+        assert!(parse_pgn("[White \"alice").is_err());
+        assert!(parse_pgn("1. e4 {unterminated").is_err());
+    }
+
+    #[test]
+    fn imports_game_and_applies_result_tag() {
+        let pgn_game = &parse_pgn("[White \"alice\"]\n[Black \"bob\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 1-0").unwrap()[0];
+        let game = pgn_to_game(pgn_game).unwrap();
+        assert_eq!(game.get_white_id(), Some("alice".to_string()));
+        assert_eq!(game.get_black_id(), Some("bob".to_string()));
+        assert!(matches!(game.get_game_status(), GameStatus::Finished));
+        assert!(matches!(game.get_game_end_condition(), GameEndCondition::BlackResigned));
+        assert_eq!(game.get_board().get_moves_history(), "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 ");
+    }
+
+    #[test]
+    fn imports_checkmate_from_board_not_tag() {
+        let pgn_game = &parse_pgn(&format!("{}1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0", PLAYERS)).unwrap()[0];
+        let game = pgn_to_game(pgn_game).unwrap();
+        assert!(matches!(game.get_game_end_condition(), GameEndCondition::WhiteCheckmatedBlack));
+    }
+
+    #[test]
+    fn unfinished_game_is_archived_not_left_ongoing() {
+        let pgn_game = &parse_pgn(&format!("{}1. e4 *", PLAYERS)).unwrap()[0];
+        let game = pgn_to_game(pgn_game).unwrap();
+        assert_eq!(game.get_game_status(), GameStatus::Archived);
+        assert_eq!(game.get_game_end_condition(), GameEndCondition::None);
+        assert!(!game.is_first_move_missed(Instant::now() + Duration::from_secs(3600), Duration::from_secs(60)));
+
+        let pgn_game = &parse_pgn(&format!("{}1. e4 e5", PLAYERS)).unwrap()[0];
+        assert_eq!(pgn_to_game(pgn_game).unwrap().get_game_status(), GameStatus::Archived);
+    }
+
+    #[test]
+    fn rejects_game_without_two_different_players() {
+        let pgn_game = &parse_pgn("1. e4 e5 1-0").unwrap()[0];
+        assert_eq!(pgn_to_game(pgn_game).unwrap_err(), "White and Black tags must name different players, both are \"?\"");
+
+        let pgn_game = &parse_pgn("[White \"alice\"]\n[Black \"alice\"]\n\n1. e4 e5 1-0").unwrap()[0];
+        assert!(pgn_to_game(pgn_game).is_err());
+
+        let pgn_game = &parse_pgn("[White \"alice\"]\n\n1. e4 e5 1-0").unwrap()[0];
+        assert_eq!(pgn_to_game(pgn_game).unwrap().get_black_id(), Some("?".to_string()));
+    }
+
+    #[test]
+    fn reports_illegal_move_with_ply() {
+        let pgn_game = &parse_pgn(&format!("{}1. e4 e5 2. Nf3 Nf3 *", PLAYERS)).unwrap()[0];
+        assert_eq!(pgn_to_game(pgn_game).unwrap_err(), "Illegal move Nf3 at ply 4");
+
+        let pgn_game = &parse_pgn(&format!("{}1. f3 e5 2. g4 Qh4# 3. a3 0-1", PLAYERS)).unwrap()[0];
+        assert_eq!(pgn_to_game(pgn_game).unwrap_err(), "Illegal move a3 at ply 5");
+    }
+
+    #[test]
+    fn imports_game_from_fen_tag() {
+        let pgn_game = &parse_pgn(&format!("{}[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 1\"]\n\n1... Kd7 2. O-O-O+ *", PLAYERS)).unwrap()[0];
+        let game = pgn_to_game(pgn_game).unwrap();
+        assert_eq!(game.get_board().get_last_move_san(), Some("O-O-O+".to_string()));
+
+        let pgn_game = &parse_pgn(&format!("{}[FEN \"not a fen\"]\n\n1. e4 *", PLAYERS)).unwrap()[0];
+        assert!(pgn_to_game(pgn_game).is_err());
+    }
+
+    #[test]
+    fn game_set_up_from_fen_exports_its_position_and_imports_back() {
+        let pgn_game = &parse_pgn(&format!("{}[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 12\"]\n\n12... Kd7 13. O-O-O+ Ke7 *", PLAYERS)).unwrap()[0];
+        let game = pgn_to_game(pgn_game).unwrap();
+
+        let pgn = game_to_pgn(&game);
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 12\"]\n"));
+        assert!(pgn.trim_end().ends_with("12... Kd7 13. O-O-O+ Ke7 *"));
+
+        let imported = pgn_to_game(&parse_pgn(&pgn).unwrap()[0]).unwrap();
+        assert_eq!(imported.get_board().board_to_fen(), game.get_board().board_to_fen());
+        assert_eq!(game_to_pgn(&imported), pgn);
+    }
+
+    #[test]
+    fn exported_game_imports_back() {
+        let mut game = Game::new("alice".to_string(), Color::White);
+        game.set_user(None, Some("bob".to_string()));
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "d4", "c6", "Nf3", "Bg4", "Be2", "e6", "O-O"] {
+            assert!(game.make_move_san(san.to_string()));
+        }
+
+        let imported = pgn_to_game(&parse_pgn(&game_to_pgn(&game)).unwrap()[0]).unwrap();
+        assert_eq!(imported.get_board().board_to_fen(), game.get_board().board_to_fen());
+        assert_eq!(game_to_pgn(&imported), game_to_pgn(&game));
+    }
+}
@@ -0,0 +1,167 @@
+// Glicko-2 ratings, every rated game is its own rating period.
+use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+use crate::time_control::TimeControl;
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+// keeps established ratings from freezing
+const MIN_DEVIATION: f64 = 45.0;
+// constrains the change in volatility
+const SYSTEM_CONSTANT: f64 = 0.5;
+const GLICKO2_SCALE: f64 = 173.7178;
+const CONVERGENCE_TOLERANCE: f64 = 0.000001;
+
+// Games are rated separately by how long they take.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RatingCategory {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+    Correspondence,
+}
+
+impl RatingCategory {
+    // By the estimated game duration, the initial time plus 40 moves of bonus time.
+    pub fn new_from_time_control(time_control: &TimeControl) -> RatingCategory {
+        match time_control.get_estimated_duration_ms() {
+            0..180_000 => RatingCategory::Bullet,
+            180_000..480_000 => RatingCategory::Blitz,
+            480_000..1_500_000 => RatingCategory::Rapid,
+            _ => RatingCategory::Classical,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RatingCategory::Bullet => "bullet",
+            RatingCategory::Blitz => "blitz",
+            RatingCategory::Rapid => "rapid",
+            RatingCategory::Classical => "classical",
+            RatingCategory::Correspondence => "correspondence",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Rating {
+    pub fn new() -> Rating {
+        Rating { rating: DEFAULT_RATING, deviation: DEFAULT_DEVIATION, volatility: DEFAULT_VOLATILITY }
+    }
+
+    // Rating after a game against the opponent, the score is 1 for a win,
+    // 0.5 for a draw and 0 for a loss.
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        let mu = (self.rating - DEFAULT_RATING) / GLICKO2_SCALE;
+        let phi = self.deviation / GLICKO2_SCALE;
+        let opponent_mu = (opponent.rating - DEFAULT_RATING) / GLICKO2_SCALE;
+        let opponent_phi = opponent.deviation / GLICKO2_SCALE;
+
+        let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+        let expected_score = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+        let variance = 1.0 / (g.powi(2) * expected_score * (1.0 - expected_score));
+        let delta = variance * g * (score - expected_score);
+
+        let volatility = Rating::get_new_volatility(phi, self.volatility, variance, delta);
+        let pre_period_phi = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / pre_period_phi.powi(2) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi.powi(2) * g * (score - expected_score);
+
+        Rating {
+            rating: GLICKO2_SCALE * new_mu + DEFAULT_RATING,
+            deviation: (GLICKO2_SCALE * new_phi).clamp(MIN_DEVIATION, DEFAULT_DEVIATION),
+            volatility,
+        }
+    }
+
+    // Step 5 of Glickman's paper, the Illinois algorithm.
+    fn get_new_volatility(phi: f64, volatility: f64, variance: f64, delta: f64) -> f64 {
+        let a = volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - variance - ex) / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+                - (x - a) / SYSTEM_CONSTANT.powi(2)
+        };
+
+        let mut lower = a;
+        let mut upper = match delta.powi(2) > phi.powi(2) + variance {
+            true => (delta.powi(2) - phi.powi(2) - variance).ln(),
+            false => {
+                let mut k = 1.0;
+                while f(a - k * SYSTEM_CONSTANT) < 0.0 {
+                    k += 1.0;
+                }
+                a - k * SYSTEM_CONSTANT
+            },
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > CONVERGENCE_TOLERANCE {
+            let middle = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_middle = f(middle);
+            if f_middle * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = middle;
+            f_upper = f_middle;
+        }
+        (lower / 2.0).exp()
+    }
+
+    // Change of the shown, rounded rating.
+    pub fn get_diff(&self, previous: &Rating) -> i32 {
+        self.rating.round() as i32 - previous.rating.round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winner_gains_what_loser_loses_between_new_players() {
+        let white = Rating::new();
+        let black = Rating::new();
+        let new_white = white.update(&black, 1.0);
+        let new_black = black.update(&white, 0.0);
+
+        assert_eq!(new_white.get_diff(&white), 162);
+        assert_eq!(new_black.get_diff(&black), -162);
+        assert!(new_white.deviation < white.deviation);
+
+        let drawn = white.update(&black, 0.5);
+        assert_eq!(drawn.get_diff(&white), 0);
+    }
+
+    #[test]
+    fn upset_moves_established_ratings_more_than_expected_result() {
+        let strong = Rating { rating: 2000.0, deviation: 60.0, volatility: DEFAULT_VOLATILITY };
+        let weak = Rating { rating: 1600.0, deviation: 60.0, volatility: DEFAULT_VOLATILITY };
+
+        let expected = strong.update(&weak, 1.0).get_diff(&strong);
+        let upset = strong.update(&weak, 0.0).get_diff(&strong);
+        assert!(expected > 0 && expected < 5);
+        assert!(upset < -15);
+    }
+
+    #[test]
+    fn rating_category_follows_estimated_duration() {
+        let category = |time_control: &str| RatingCategory::new_from_time_control(&TimeControl::new_from_string(time_control).unwrap());
+        assert_eq!(category("2+1"), RatingCategory::Bullet);
+        assert_eq!(category("5+3"), RatingCategory::Blitz);
+        assert_eq!(category("10+5"), RatingCategory::Rapid);
+        assert_eq!(category("30+0"), RatingCategory::Classical);
+    }
+}
@@ -1,5 +1,7 @@
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
+use crate::chess_engine::color::Color;
+use crate::rating::RatingCategory;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum RequestEnum {
@@ -8,38 +10,214 @@ pub enum RequestEnum {
     JoinGameRequest (JoinGameRequest),
     AuthorizeWebsocketConnectionRequest (AuthorizeWebsocketConnectionRequest),
     MakeMoveRequest (MakeMoveRequest),
+    ImportPgnRequest (ImportPgnRequest),
+    ClaimDrawRequest (ClaimDrawRequest),
+    ResignRequest (ResignRequest),
+    OfferDrawRequest (OfferDrawRequest),
+    AcceptDrawRequest (AcceptDrawRequest),
+    DeclineDrawRequest (DeclineDrawRequest),
+    AbortGameRequest (AbortGameRequest),
+    TakebackRequest (TakebackRequest),
+    AcceptTakebackRequest (AcceptTakebackRequest),
+    DeclineTakebackRequest (DeclineTakebackRequest),
+    SpectateGameRequest (SpectateGameRequest),
+    ChatMessageRequest (ChatMessageRequest),
+    SubscribeLobbyRequest (SubscribeLobbyRequest),
+    JoinQueueRequest (JoinQueueRequest),
+    LeaveQueueRequest (LeaveQueueRequest),
+    RegisterRequest (RegisterRequest),
+    LoginRequest (LoginRequest),
 }
 
+// The creator is the logged in user.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateGameRequest {
-    pub user_id: String,
-    pub color: String,
+    // "white", "black" or "random", a random color is picked when the opponent joins
+    pub color: Color,
+    // e.g. "5+3", "15d5" or "40/90+30, then G/30", untimed if missing
+    #[serde(default)]
+    pub time_control: Option<String>,
+    // correspondence game with days per move instead of a clock
+    #[serde(default)]
+    pub days_per_move: Option<u32>,
+    // rated games need a time control or days per move
+    #[serde(default)]
+    pub rated: bool,
+    // not listed in the lobby, joined with the returned join token
+    #[serde(default)]
+    pub private: bool,
+    // direct challenge, only this user can join or decline
+    #[serde(default)]
+    pub opponent_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetGamesRequest { }
 
+// Query of GET /seeks, every filter is optional.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GetSeeksRequest {
+    #[serde(default)]
+    pub rated: Option<bool>,
+    #[serde(default)]
+    pub category: Option<RatingCategory>,
+    #[serde(default)]
+    pub min_rating: Option<i32>,
+    #[serde(default)]
+    pub max_rating: Option<i32>,
+    // color the creator plays
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+// The joining user is the logged in user.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JoinGameRequest {
     pub game_id: Uuid,
-    pub user_id: String,
+    // needed for private games
+    #[serde(default)]
+    pub join_token: Option<String>,
+}
+
+// The challenged user is the logged in user.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeclineChallengeRequest {
+    pub game_id: Uuid,
 }
 
+// The user is taken from the token returned by /login.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuthorizeWebsocketConnectionRequest {
     pub game_id: Uuid,
-    pub user_id: String,
+    pub token: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MakeMoveRequest {
     pub game_id: Uuid,
     pub user_id: String,
-    pub from: String,
-    pub to: String,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
     pub promotion_piece: Option<String>,
+    #[serde(default)]
+    pub san: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RegisterRequest {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginRequest {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportPgnRequest {
+    pub pgn: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClaimDrawRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResignRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OfferDrawRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AcceptDrawRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeclineDrawRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AbortGameRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TakebackRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AcceptTakebackRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeclineTakebackRequest {
+    pub game_id: Uuid,
+    pub user_id: String,
+}
+
+// Subscribes the connection to a game read-only, no user is needed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpectateGameRequest {
+    pub game_id: Uuid,
+}
+
+// Players write to the players' room, spectator connections to the spectators' room.
+// Spectators are named by the user of the token, or anonymous without one.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChatMessageRequest {
+    pub game_id: Uuid,
+    #[serde(default)]
+    pub user_id: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    pub message: String,
+}
+
+// Subscribes the connection to seeks being added and removed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscribeLobbyRequest { }
+
+// Quick pairing, the user is taken from the token returned by /login.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JoinQueueRequest {
+    pub token: String,
+    // e.g. "5+3"
+    pub time_control: String,
+    #[serde(default)]
+    pub rated: bool,
+}
+
+// Takes the connection's user out of the queue.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LeaveQueueRequest { }
+
 //
 // #[derive(Serialize, Deserialize, Debug)]
 // pub struct CreateGameRequest {
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::Json;
 use axum::response::{Response as AxumResponse, IntoResponse};
 use uuid::Uuid;
@@ -8,12 +8,35 @@ use serde::{Deserialize, Serialize};
 use crate::game::Game;
 use crate::game_status::GameStatus;
 use crate::game_end_condition::GameEndCondition;
+use crate::chat::{ChatMessage, ChatRoom};
+use crate::lobby::Seek;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Response {
-    CreateGameResponse { game_id: Uuid, message: String, },
+    // join_token is only set for private games
+    CreateGameResponse { game_id: Uuid, join_token: Option<String>, message: String, },
     GetGamesResponse { game_ids: Vec<Uuid>, },
+    // total is the number of seeks matching the filter
+    GetSeeksResponse { seeks: Vec<Seek>, total: usize, },
+    SeekAddedResponse { seek: Seek, },
+    SeekRemovedResponse { game_id: Uuid, },
+    // state is "Offered", "Accepted", "Declined" or "Cancelled"
+    ChallengeResponse { game_id: Uuid, challenger_id: String, challenged_user_id: String, state: String, message: String, },
+    // state is "Joined", "Left" or "Failed"
+    QueueResponse { connection_id: SocketAddr, state: String, time_control: String, message: String, },
+    // the player authorizes for the game next
+    MatchFoundResponse {
+        game_id: Uuid,
+        connection_id: SocketAddr,
+        white_id: String,
+        black_id: String,
+        time_control: String,
+        rated: bool,
+    },
     JoinGameResponse { game_id: Uuid, message: String, },
+    RegisterResponse { user_id: String, message: String, },
+    // the token goes into the "Authorization: Bearer <token>" header of later requests
+    LoginResponse { user_id: String, token: String, message: String, },
     AuthorizeWebsocketConnectionResponse {
         game_id: Uuid,
         user_id: String,
@@ -29,16 +52,49 @@ pub enum Response {
         board: HashMap<String, (String, Vec<String>)>,
         game_status: GameStatus,
         game_end_condition: GameEndCondition,
+        san: String,
+        white_time_ms: Option<u64>,
+        black_time_ms: Option<u64>,
+        spectator_count: usize,
+        // rating changes of a finished rated game
+        white_rating_diff: Option<i32>,
+        black_rating_diff: Option<i32>,
+    },
+    GetGamePgnResponse { game_id: Uuid, pgn: String, },
+    ImportPgnResponse { game_ids: Vec<Uuid>, errors: Vec<String>, },
+    GameEndedResponse {
+        game_id: Uuid,
+        message: String,
+        game_status: GameStatus,
+        game_end_condition: GameEndCondition,
+        white_rating_diff: Option<i32>,
+        black_rating_diff: Option<i32>,
     },
+    // state is "Offered", "Declined" or "Expired"; user_id is the user who caused it
+    DrawOfferResponse { game_id: Uuid, user_id: String, state: String, message: String, },
+    // state is "Requested", "Accepted", "Declined" or "Expired"; user_id is the user who caused it
+    TakebackResponse { game_id: Uuid, user_id: String, state: String, message: String, },
+    SpectateGameResponse {
+        game_id: Uuid,
+        connection_id: SocketAddr,
+        board: HashMap<String, (String, Vec<String>)>,
+        spectator_count: usize,
+        message: String,
+    },
+    SpectatorCountResponse { game_id: Uuid, spectator_count: usize, },
+    ChatMessageResponse { game_id: Uuid, room: ChatRoom, user_id: String, message: String, sent_at_ms: u64, },
+    // earlier messages of the room, sent to a connection when it joins the game
+    ChatHistoryResponse { game_id: Uuid, connection_id: SocketAddr, room: ChatRoom, messages: Vec<ChatMessage>, },
     RequestFailedResponse { message: String, }
 }
 
 impl IntoResponse for Response {
     fn into_response(self) -> AxumResponse {
         match self {
-            Response::CreateGameResponse { game_id, message } => {
+            Response::CreateGameResponse { game_id, join_token, message } => {
                 let body = Json(serde_json::json!({
                     "game_id": game_id,
+                    "join_token": join_token,
                     "message": message,
                 }));
                 (StatusCode::OK, body).into_response()
@@ -49,6 +105,55 @@ impl IntoResponse for Response {
                 }));
                 (StatusCode::OK, body).into_response()
             },
+            Response::GetSeeksResponse { seeks, total } => {
+                let body = Json(serde_json::json!({
+                    "seeks": seeks,
+                    "total": total,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::SeekAddedResponse { seek } => {
+                let body = Json(serde_json::json!({
+                    "seek": seek,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::SeekRemovedResponse { game_id } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::ChallengeResponse { game_id, challenger_id, challenged_user_id, state, message } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "challenger_id": challenger_id,
+                    "challenged_user_id": challenged_user_id,
+                    "state": state,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::QueueResponse { connection_id, state, time_control, message } => {
+                let body = Json(serde_json::json!({
+                    "connection_id": connection_id,
+                    "state": state,
+                    "time_control": time_control,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::MatchFoundResponse { game_id, connection_id, white_id, black_id, time_control, rated } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "connection_id": connection_id,
+                    "white_id": white_id,
+                    "black_id": black_id,
+                    "time_control": time_control,
+                    "rated": rated,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
             Response::JoinGameResponse { game_id, message } => {
                 let body = Json(serde_json::json!({
                     "game_id": game_id,
@@ -74,6 +179,12 @@ impl IntoResponse for Response {
                 board,
                 game_status,
                 game_end_condition,
+                san,
+                white_time_ms,
+                black_time_ms,
+                spectator_count,
+                white_rating_diff,
+                black_rating_diff,
             } => {
                 let body = Json(serde_json::json!({
                     "game_id": game_id,
@@ -83,6 +194,102 @@ impl IntoResponse for Response {
                     "board": board,
                     "game_status": game_status.to_string(),
                     "game_end_condition": game_end_condition.to_string(),
+                    "san": san,
+                    "white_time_ms": white_time_ms,
+                    "black_time_ms": black_time_ms,
+                    "spectator_count": spectator_count,
+                    "white_rating_diff": white_rating_diff,
+                    "black_rating_diff": black_rating_diff,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::GetGamePgnResponse { game_id: _, pgn } => {
+                (StatusCode::OK, [(header::CONTENT_TYPE, "application/x-chess-pgn")], pgn).into_response()
+            },
+            Response::ImportPgnResponse { game_ids, errors } => {
+                let body = Json(serde_json::json!({
+                    "game_ids": game_ids,
+                    "errors": errors,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::GameEndedResponse { game_id, message, game_status, game_end_condition, white_rating_diff, black_rating_diff } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "message": message,
+                    "game_status": game_status.to_string(),
+                    "game_end_condition": game_end_condition.to_string(),
+                    "white_rating_diff": white_rating_diff,
+                    "black_rating_diff": black_rating_diff,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::DrawOfferResponse { game_id, user_id, state, message } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "user_id": user_id,
+                    "state": state,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::TakebackResponse { game_id, user_id, state, message } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "user_id": user_id,
+                    "state": state,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::SpectateGameResponse { game_id, connection_id, board, spectator_count, message } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "connection_id": connection_id,
+                    "board": board,
+                    "spectator_count": spectator_count,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::SpectatorCountResponse { game_id, spectator_count } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "spectator_count": spectator_count,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::ChatMessageResponse { game_id, room, user_id, message, sent_at_ms } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "room": room,
+                    "user_id": user_id,
+                    "message": message,
+                    "sent_at_ms": sent_at_ms,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::ChatHistoryResponse { game_id, connection_id, room, messages } => {
+                let body = Json(serde_json::json!({
+                    "game_id": game_id,
+                    "connection_id": connection_id,
+                    "room": room,
+                    "messages": messages,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::RegisterResponse { user_id, message } => {
+                let body = Json(serde_json::json!({
+                    "user_id": user_id,
+                    "message": message,
+                }));
+                (StatusCode::OK, body).into_response()
+            },
+            Response::LoginResponse { user_id, token, message } => {
+                let body = Json(serde_json::json!({
+                    "user_id": user_id,
+                    "token": token,
+                    "message": message,
                 }));
                 (StatusCode::OK, body).into_response()
             },
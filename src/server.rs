@@ -9,7 +9,7 @@ use tokio::net::TcpListener;
 use tokio_websockets::ServerBuilder;
 use crate::game::Game;
 use crate::game_repository::GameRepository;
-use crate::http_server::{get_games_from_dict, create_game, join_game};
+use crate::http_server::{get_games_from_dict, get_seeks, create_game, join_game, get_challenges, decline_challenge, get_game_pgn, import_pgn, register, login};
 use futures_util::{SinkExt, StreamExt};
 use crate::connection_manager::ConnectionManager;
 // use crate::websocket_server::run_websocket_server;
@@ -43,9 +43,16 @@ pub async fn run_server() {
 
 async fn run_http_server(game_manager: Arc<RwLock<GameManager>>) {
     let api_router = Router::new()
+        .route("/register", post(register))
+        .route("/login", post(login))
         .route("/get_games", get(get_games_from_dict))
+        .route("/seeks", get(get_seeks))
         .route("/create_game", post(create_game))
         .route("/join_game", put(join_game))
+        .route("/challenges", get(get_challenges))
+        .route("/decline_challenge", put(decline_challenge))
+        .route("/games/:game_id/pgn", get(get_game_pgn))
+        .route("/import_pgn", post(import_pgn))
         .with_state(game_manager);
 
     let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
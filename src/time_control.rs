@@ -0,0 +1,441 @@
+// Time controls and chess clocks. Times are kept in milliseconds.
+use std::fmt;
+use std::time::Instant;
+use crate::chess_engine::color::ActiveColor;
+
+// longest time of a stage, correspondence games use days per move instead
+const MAX_STAGE_MINUTES: f64 = 24.0 * 60.0;
+// moves of the first stage counted in the estimated game duration
+const ESTIMATED_MOVES: u64 = 40;
+
+// How the bonus time of a stage is given.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeControlMode {
+    // bonus added after every move
+    Increment,
+    // US delay: the clock starts running only after the bonus time has passed
+    SimpleDelay,
+    // time used is given back after the move, up to the bonus time
+    BronsteinDelay,
+}
+
+impl TimeControlMode {
+    fn separator(&self) -> char {
+        match self {
+            TimeControlMode::Increment => '+',
+            TimeControlMode::SimpleDelay => 'd',
+            TimeControlMode::BronsteinDelay => 'b',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControlStage {
+    // moves to make within the stage, None for the rest of the game
+    moves: Option<u32>,
+    time_ms: u64,
+    bonus_ms: u64,
+    mode: TimeControlMode,
+}
+
+impl TimeControlStage {
+    // "90+30", "40/90+30", "G/30", "5d3" or "5b3"
+    fn new_from_string(stage: &str) -> Option<TimeControlStage> {
+        let stage = stage.trim();
+        let (moves, rest) = match stage.split_once('/') {
+            Some(("G", rest)) => (None, rest),
+            Some((moves, rest)) => (Some(moves.trim().parse::<u32>().ok().filter(|moves| *moves > 0)?), rest),
+            None => (None, stage),
+        };
+
+        let (minutes, mode, bonus) = match rest.find(['+', 'd', 'b']) {
+            Some(index) => {
+                let mode = match &rest[index..index + 1] {
+                    "+" => TimeControlMode::Increment,
+                    "d" => TimeControlMode::SimpleDelay,
+                    _ => TimeControlMode::BronsteinDelay,
+                };
+                (&rest[..index], mode, rest[index + 1..].trim().parse::<u64>().ok()?)
+            },
+            None => (rest, TimeControlMode::Increment, 0),
+        };
+        let minutes: f64 = minutes.trim().parse().ok()?;
+        if !minutes.is_finite() || !(0.0..=MAX_STAGE_MINUTES).contains(&minutes) || (minutes == 0.0 && bonus == 0) {
+            return None;
+        }
+
+        let time_ms = (minutes * 60_000.0).round() as u64;
+        let bonus_ms = bonus.checked_mul(1000)?;
+        // the estimated game duration must fit as well
+        bonus_ms.checked_mul(ESTIMATED_MOVES)?.checked_add(time_ms)?;
+        Some(TimeControlStage { moves, time_ms, bonus_ms, mode })
+    }
+}
+
+impl fmt::Display for TimeControlStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{}/", moves)?;
+        }
+        write!(f, "{}", self.time_ms as f64 / 60_000.0)?;
+        if self.bonus_ms > 0 || self.mode != TimeControlMode::Increment {
+            write!(f, "{}{}", self.mode.separator(), self.bonus_ms / 1000)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControl {
+    stages: Vec<TimeControlStage>,
+}
+
+impl TimeControl {
+    // Stages are separated by commas, e.g. "5+3" or "40/90+30, then G/30".
+    // A last stage with a move count repeats.
+    pub fn new_from_string(time_control: &str) -> Result<TimeControl, String> {
+        let invalid = || format!("Invalid time control: {}", time_control);
+        let mut stages: Vec<TimeControlStage> = Vec::new();
+        for stage in time_control.split(',') {
+            let stage = stage.trim();
+            let stage = stage.strip_prefix("then").unwrap_or(stage);
+            // only the last stage may last for the rest of the game
+            if stages.last().is_some_and(|last| last.moves.is_none()) {
+                return Err(invalid());
+            }
+            stages.push(TimeControlStage::new_from_string(stage).ok_or_else(invalid)?);
+        }
+        Ok(TimeControl { stages })
+    }
+
+    pub fn get_initial_ms(&self) -> u64 {
+        self.stages[0].time_ms
+    }
+
+    // Time a side uses in a game of 40 moves of the first stage.
+    pub fn get_estimated_duration_ms(&self) -> u64 {
+        self.stages[0].time_ms + ESTIMATED_MOVES * self.stages[0].bonus_ms
+    }
+
+    // Stage in which the side makes its next move.
+    fn get_stage(&self, moves_made: u32) -> &TimeControlStage {
+        let mut stage_end = 0;
+        for stage in &self.stages {
+            match stage.moves {
+                Some(moves) if moves_made >= stage_end + moves => stage_end += moves,
+                _ => return stage,
+            }
+        }
+        self.stages.last().unwrap()
+    }
+
+    // Time added when the side completes a stage with its move.
+    fn get_time_added_after(&self, moves_made: u32) -> u64 {
+        let mut stage_end = 0;
+        for (index, stage) in self.stages.iter().enumerate() {
+            match stage.moves {
+                None => return 0,
+                Some(moves) => {
+                    stage_end += moves;
+                    if moves_made == stage_end {
+                        return self.stages.get(index + 1).unwrap_or(stage).time_ms;
+                    }
+                    if moves_made < stage_end {
+                        return 0;
+                    }
+                },
+            }
+        }
+
+        // the last stage repeats
+        let last = self.stages.last().unwrap();
+        match last.moves {
+            Some(moves) if (moves_made - stage_end).is_multiple_of(moves) => last.time_ms,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, stage) in self.stages.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", then ")?;
+                if stage.moves.is_none() {
+                    write!(f, "G/")?;
+                }
+            }
+            write!(f, "{}", stage)?;
+        }
+        Ok(())
+    }
+}
+
+// Clocks of both sides. The clock starts with the first move, so the time
+// before White's first move is not counted.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    time_control: TimeControl,
+    // white, black
+    remaining_ms: [u64; 2],
+    moves_made: [u32; 2],
+    // bonus time given for each move of the side, removed when it is taken back
+    bonuses_ms: [Vec<u64>; 2],
+    // side whose clock is running and since when
+    turn: Option<(ActiveColor, Instant)>,
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Clock {
+        let initial_ms = time_control.get_initial_ms();
+        Clock {
+            time_control,
+            remaining_ms: [initial_ms, initial_ms],
+            moves_made: [0, 0],
+            bonuses_ms: [Vec::new(), Vec::new()],
+            turn: None,
+        }
+    }
+
+    fn index(color: &ActiveColor) -> usize {
+        match color {
+            ActiveColor::White => 0,
+            ActiveColor::Black => 1,
+        }
+    }
+
+    pub fn get_time_control(&self) -> &TimeControl {
+        &self.time_control
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.turn.is_some()
+    }
+
+    // Remaining time of the side, including the running turn.
+    pub fn get_remaining_ms(&self, color: &ActiveColor, now: Instant) -> u64 {
+        let remaining_ms = self.remaining_ms[Clock::index(color)];
+        match &self.turn {
+            Some((turn_color, started_at)) if turn_color.equals(color.clone()) => {
+                let elapsed_ms = now.saturating_duration_since(*started_at).as_millis() as u64;
+                let stage = self.time_control.get_stage(self.moves_made[Clock::index(color)]);
+                match stage.mode {
+                    TimeControlMode::SimpleDelay => remaining_ms.saturating_sub(elapsed_ms.saturating_sub(stage.bonus_ms)),
+                    _ => remaining_ms.saturating_sub(elapsed_ms),
+                }
+            },
+            _ => remaining_ms,
+        }
+    }
+
+    // Stops the mover's clock, gives the bonus time and starts the opponent's clock.
+    // The first move of the game takes no time but still gets the increment.
+    pub fn press(&mut self, mover: &ActiveColor, now: Instant) {
+        let index = Clock::index(mover);
+        let elapsed_ms = match &self.turn {
+            Some((_, started_at)) => now.saturating_duration_since(*started_at).as_millis() as u64,
+            None => 0,
+        };
+        let stage = self.time_control.get_stage(self.moves_made[index]);
+        let bonus_ms = match stage.mode {
+            TimeControlMode::Increment => stage.bonus_ms,
+            TimeControlMode::SimpleDelay => 0,
+            TimeControlMode::BronsteinDelay => elapsed_ms.min(stage.bonus_ms),
+        };
+        self.remaining_ms[index] = self.get_remaining_ms(mover, now).saturating_add(bonus_ms);
+        self.bonuses_ms[index].push(bonus_ms);
+
+        self.moves_made[index] += 1;
+        self.remaining_ms[index] += self.time_control.get_time_added_after(self.moves_made[index]);
+        self.turn = Some((mover.next(), now));
+    }
+
+    // Undoes the last press of the given side and runs its clock again. Time
+    // already spent is not given back, the bonus time of the move is removed.
+    pub fn take_back(&mut self, mover: &ActiveColor, now: Instant) {
+        let index = Clock::index(mover);
+        if self.moves_made[index] == 0 {
+            return;
+        }
+        if let Some((color, _)) = self.turn.clone() {
+            self.remaining_ms[Clock::index(&color)] = self.get_remaining_ms(&color, now);
+        }
+        let time_added = self.time_control.get_time_added_after(self.moves_made[index])
+            + self.bonuses_ms[index].pop().unwrap_or(0);
+        self.remaining_ms[index] = self.remaining_ms[index].saturating_sub(time_added);
+        self.moves_made[index] -= 1;
+        self.turn = match self.moves_made.iter().sum::<u32>() {
+            0 => None,
+            _ => Some((mover.clone(), now)),
+        };
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((color, _)) = self.turn.clone() {
+            self.remaining_ms[Clock::index(&color)] = self.get_remaining_ms(&color, now);
+        }
+        self.turn = None;
+    }
+
+    // Side whose time has run out.
+    pub fn get_flagged_color(&self, now: Instant) -> Option<ActiveColor> {
+        match &self.turn {
+            Some((color, _)) if self.get_remaining_ms(color, now) == 0 => Some(color.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn play(clock: &mut Clock, start: Instant, seconds_per_move: &[u64]) -> Instant {
+        let mut now = start;
+        let mut mover = ActiveColor::White;
+        clock.press(&mover, now);
+        for seconds in seconds_per_move {
+            mover = mover.next();
+            now += Duration::from_secs(*seconds);
+            clock.press(&mover, now);
+        }
+        now
+    }
+
+    #[test]
+    fn parses_time_control() {
+        let time_control = TimeControl::new_from_string("5+3").unwrap();
+        assert_eq!(time_control.get_initial_ms(), 300_000);
+        assert_eq!(time_control.to_string(), "5+3");
+        assert_eq!(TimeControl::new_from_string("0.5+0").unwrap().get_initial_ms(), 30_000);
+
+        for (time_control, expected) in [
+            ("5", "5"),
+            ("G/30", "30"),
+            ("5d3", "5d3"),
+            ("15b10", "15b10"),
+            ("40/90+30, then G/30", "40/90+30, then G/30"),
+            ("40/120,20/60,G/30+30", "40/120, then 20/60, then G/30+30"),
+        ] {
+            assert_eq!(TimeControl::new_from_string(time_control).unwrap().to_string(), expected);
+        }
+
+        for invalid in ["", "5+", "+3", "a+3", "5+-1", "0+0", "-1+3", "0/90", "G/30, then 40/90", "40/90+30,"] {
+            assert!(TimeControl::new_from_string(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn rejects_time_control_that_would_overflow() {
+        for invalid in ["5+99999999999999999", "5+18446744073709551615", "5d461168601842738", "1e300+0", "1441+0"] {
+            assert!(TimeControl::new_from_string(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(TimeControl::new_from_string("1440+0").unwrap().get_estimated_duration_ms(), 86_400_000);
+    }
+
+    #[test]
+    fn clock_deducts_elapsed_time_and_adds_increment() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new_from_string("1+2").unwrap());
+
+        // the first move starts the clock without deducting time, but adds the increment
+        clock.press(&ActiveColor::White, start);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, start), 62_000);
+
+        let now = start + Duration::from_secs(10);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now), 50_000);
+        clock.press(&ActiveColor::Black, now);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now), 52_000);
+
+        clock.stop(now + Duration::from_secs(5));
+        assert!(!clock.is_running());
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, now + Duration::from_secs(60)), 57_000);
+    }
+
+    #[test]
+    fn simple_delay_runs_the_clock_after_the_delay() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new_from_string("1d5").unwrap());
+        clock.press(&ActiveColor::White, start);
+
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, start + Duration::from_secs(4)), 60_000);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, start + Duration::from_secs(8)), 57_000);
+        clock.press(&ActiveColor::Black, start + Duration::from_secs(3));
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, start + Duration::from_secs(3)), 60_000);
+
+        clock.press(&ActiveColor::White, start + Duration::from_secs(3));
+        assert!(clock.get_flagged_color(start + Duration::from_secs(67)).is_none());
+        assert!(matches!(clock.get_flagged_color(start + Duration::from_secs(68)), Some(ActiveColor::Black)));
+    }
+
+    #[test]
+    fn bronstein_delay_gives_back_used_time_up_to_the_delay() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new_from_string("1b5").unwrap());
+        let now = play(&mut clock, start, &[3, 8]);
+
+        // black used 3 seconds and got them back, white used 8 and got 5 back
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now), 60_000);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, now), 57_000);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now + Duration::from_secs(10)), 50_000);
+    }
+
+    #[test]
+    fn multi_stage_control_adds_time_after_the_move_count() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new_from_string("2/1, then G/1+10").unwrap());
+
+        // white: first move is free, second move takes 10 seconds and completes the first stage
+        let now = play(&mut clock, start, &[0, 10]);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, now), 110_000);
+
+        // the second stage has an increment
+        clock.press(&ActiveColor::Black, now);
+        clock.press(&ActiveColor::White, now + Duration::from_secs(10));
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, now), 110_000);
+    }
+
+    #[test]
+    fn repeating_last_stage_adds_time_every_period() {
+        let time_control = TimeControl::new_from_string("2/1").unwrap();
+        assert_eq!(time_control.get_time_added_after(1), 0);
+        assert_eq!(time_control.get_time_added_after(2), 60_000);
+        assert_eq!(time_control.get_time_added_after(3), 0);
+        assert_eq!(time_control.get_time_added_after(4), 60_000);
+    }
+
+    #[test]
+    fn take_back_runs_the_movers_clock_again() {
+        let mut clock = Clock::new(TimeControl::new_from_string("5+2").unwrap());
+        let start = Instant::now();
+        let now = play(&mut clock, start, &[0, 10, 20]);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now), 284_000);
+        clock.take_back(&ActiveColor::Black, now + Duration::from_secs(5));
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::White, now + Duration::from_secs(9)), 289_000);
+        // the increment of the taken back move is removed, the 20 seconds stay spent
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now + Duration::from_secs(6)), 281_000);
+
+        // a Bronstein bonus is removed as well
+        let mut clock = Clock::new(TimeControl::new_from_string("1b5").unwrap());
+        let now = play(&mut clock, start, &[3]);
+        clock.take_back(&ActiveColor::Black, now);
+        assert_eq!(clock.get_remaining_ms(&ActiveColor::Black, now), 57_000);
+
+        let mut clock = Clock::new(TimeControl::new_from_string("5+2").unwrap());
+        clock.press(&ActiveColor::White, start);
+        clock.take_back(&ActiveColor::White, start);
+        assert!(!clock.is_running());
+    }
+
+    #[test]
+    fn clock_flags_side_out_of_time() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new_from_string("1+0").unwrap());
+        assert!(clock.get_flagged_color(start + Duration::from_secs(120)).is_none());
+
+        clock.press(&ActiveColor::White, start);
+        assert!(clock.get_flagged_color(start + Duration::from_secs(59)).is_none());
+        assert!(matches!(clock.get_flagged_color(start + Duration::from_secs(60)), Some(ActiveColor::Black)));
+    }
+}
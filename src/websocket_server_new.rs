@@ -6,15 +6,20 @@ use std::{
     sync::Arc,
 };
 use std::ops::Deref;
+use std::time::{Duration, Instant, SystemTime};
 use futures_channel::mpsc::{unbounded, UnboundedSender};
 use futures_util::{future, pin_mut, stream::TryStreamExt, StreamExt};
 
 use tokio::sync::{Mutex, RwLock};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::net::{TcpListener, TcpStream};
 use tokio_tungstenite::tungstenite::protocol::Message;
 
+use crate::game::Game;
 use crate::game_manager::GameManager;
-use crate::request::{RequestEnum, AuthorizeWebsocketConnectionRequest, MakeMoveRequest};
+use crate::request::{RequestEnum, AuthorizeWebsocketConnectionRequest, MakeMoveRequest, ClaimDrawRequest, ResignRequest,
+    OfferDrawRequest, AcceptDrawRequest, DeclineDrawRequest, AbortGameRequest, TakebackRequest, AcceptTakebackRequest,
+    DeclineTakebackRequest, SpectateGameRequest, ChatMessageRequest, GetSeeksRequest, JoinQueueRequest};
 use serde_json::from_str;
 use tokio_postgres::types::ToSql;
 use uuid::Uuid;
@@ -25,6 +30,13 @@ use crate::Board;
 use crate::chess_engine::color::ActiveColor;
 use crate::game_end_condition::GameEndCondition;
 use crate::game_status::GameStatus;
+use crate::chat::{ChatMessage, ChatRoom, ANONYMOUS_SPECTATOR};
+use crate::chess_engine::color::Color;
+use crate::matchmaking::QueueEntry;
+use crate::rating::{RatingCategory, DEFAULT_RATING};
+use crate::time_control::TimeControl;
+
+const DEFAULT_ABORT_WINDOW_SECONDS: u64 = 60;
 
 type Tx = UnboundedSender<Message>;
 type PeerMap = Arc<Mutex<HashMap<SocketAddr, Tx>>>;
@@ -45,6 +57,25 @@ pub async fn run_websocket_server_new(game_manager: Arc<RwLock<GameManager>>) ->
         }
     });
 
+    // seeks are added and removed by the HTTP server
+    let mut lobby_subscriber = game_manager.read().await.lobby.subscribe();
+    let event_service_clone = Arc::clone(&event_service);
+    tokio::spawn(async move {
+        loop {
+            match lobby_subscriber.recv().await {
+                Ok(response) => event_service_clone.read().await.publish(&response).await,
+                // skipped updates are in the next GET /seeks
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(run_clock_watcher(Arc::clone(&game_manager), Arc::clone(&event_service)));
+    tokio::spawn(run_deadline_sweeper(Arc::clone(&game_manager), Arc::clone(&event_service)));
+    tokio::spawn(run_matchmaker(Arc::clone(&game_manager), Arc::clone(&event_service)));
+    tokio::spawn(run_abort_watcher(Arc::clone(&game_manager), Arc::clone(&event_service), abort_window()));
+
     let state = PeerMap::new(Mutex::new(HashMap::new()));
 
     // Create the event loop and TCP listener we'll accept connections on.
@@ -87,14 +118,119 @@ async fn handle_connection(game_manager: Arc<RwLock<GameManager>>, event_service
                 println!("{}", error);
                 return Ok(());
             }
-            match request.unwrap() {
-                RequestEnum::AuthorizeWebsocketConnectionRequest(AuthorizeWebsocketConnectionRequest { game_id, user_id }) => {
+            let request = request.unwrap();
+            // spectator connections are read-only
+            let is_spectator = game_manager_clone.read().await.connection_manager.is_spectator_connection(&addr);
+            if is_spectator && !matches!(request, RequestEnum::SpectateGameRequest(_) | RequestEnum::ChatMessageRequest(_) | RequestEnum::SubscribeLobbyRequest(_)) {
+                send_response(&tx_clone, &Response::RequestFailedResponse {
+                    message: "Spectators cannot make moves or change the game".to_string(),
+                });
+                return Ok(());
+            }
+            // game actions are only accepted from a connection authorized as the acting user
+            if let Some(user_id) = get_acting_user_id(&request) {
+                if !game_manager_clone.read().await.connection_manager.is_user_connection(user_id, &addr) {
+                    send_response(&tx_clone, &Response::RequestFailedResponse {
+                        message: "Connection is not authorized for this user".to_string(),
+                    });
+                    return Ok(());
+                }
+            }
+            match request {
+                RequestEnum::SpectateGameRequest(SpectateGameRequest { game_id }) => {
+                    spectate(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, addr, tx_clone).await;
+                },
+
+                RequestEnum::ChatMessageRequest(request) => {
+                    chat(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), request, addr, tx_clone).await;
+                },
+
+                RequestEnum::SubscribeLobbyRequest(_) => {
+                    subscribe_lobby(Arc::clone(&game_manager_clone), addr, tx_clone).await;
+                },
+
+                RequestEnum::JoinQueueRequest(JoinQueueRequest { token, time_control, rated }) => {
+                    join_queue(Arc::clone(&game_manager_clone), token, time_control, rated, addr, tx_clone).await;
+                },
+
+                RequestEnum::LeaveQueueRequest(_) => {
+                    let response = match game_manager_clone.write().await.matchmaking_queue.leave_by_connection(&addr) {
+                        true => Response::QueueResponse {
+                            connection_id: addr,
+                            state: "Left".to_string(),
+                            time_control: "".to_string(),
+                            message: "Left the queue".to_string(),
+                        },
+                        false => Response::RequestFailedResponse { message: "Not in the queue".to_string() },
+                    };
+                    send_response(&tx_clone, &response);
+                },
+
+                RequestEnum::AuthorizeWebsocketConnectionRequest(AuthorizeWebsocketConnectionRequest { game_id, token }) => {
+                    let user_id = match game_manager_clone.read().await.token_signer.verify_token(&token, SystemTime::now()) {
+                        Ok(user_id) => user_id,
+                        Err(message) => {
+                            send_response(&tx_clone, &Response::RequestFailedResponse { message });
+                            return Ok(());
+                        },
+                    };
                     authorize(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, addr, tx_clone).await;
                 },
 
-                RequestEnum::MakeMoveRequest(MakeMoveRequest { game_id, user_id, from, to , promotion_piece}) => {
-                    let r = make_move(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, from, to, promotion_piece).await;
-                    // return Ok(());
+                RequestEnum::MakeMoveRequest(request) => {
+                    let response = make_move(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), request).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::ClaimDrawRequest(ClaimDrawRequest { game_id, user_id }) => {
+                    let response = claim_draw(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::ResignRequest(ResignRequest { game_id, user_id }) => {
+                    let response = resign(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::OfferDrawRequest(OfferDrawRequest { game_id, user_id }) => {
+                    let response = Response::DrawOfferResponse { game_id, user_id: user_id.clone(), state: "Offered".to_string(), message: format!("{} offered a draw", user_id) };
+                    let response = update_offer(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, response, |game, user_id| game.offer_draw(user_id)).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::AcceptDrawRequest(AcceptDrawRequest { game_id, user_id }) => {
+                    let message = format!("{} accepted the draw offer", user_id);
+                    let response = end_game(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, message, |game, user_id| game.accept_draw(user_id)).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::DeclineDrawRequest(DeclineDrawRequest { game_id, user_id }) => {
+                    let response = Response::DrawOfferResponse { game_id, user_id: user_id.clone(), state: "Declined".to_string(), message: format!("{} declined the draw offer", user_id) };
+                    let response = update_offer(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, response, |game, user_id| game.decline_draw(user_id)).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::AbortGameRequest(AbortGameRequest { game_id, user_id }) => {
+                    let message = format!("{} aborted the game", user_id);
+                    let response = end_game(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, message, |game, user_id| game.abort(user_id)).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::TakebackRequest(TakebackRequest { game_id, user_id }) => {
+                    let response = Response::TakebackResponse { game_id, user_id: user_id.clone(), state: "Requested".to_string(), message: format!("{} requested a takeback", user_id) };
+                    let response = update_offer(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, response, |game, user_id| game.request_takeback(user_id)).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::AcceptTakebackRequest(AcceptTakebackRequest { game_id, user_id }) => {
+                    let response = accept_takeback(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id).await;
+                    send_if_failed(&tx_clone, response);
+                },
+
+                RequestEnum::DeclineTakebackRequest(DeclineTakebackRequest { game_id, user_id }) => {
+                    let response = Response::TakebackResponse { game_id, user_id: user_id.clone(), state: "Declined".to_string(), message: format!("{} declined the takeback", user_id) };
+                    let response = update_offer(Arc::clone(&game_manager_clone), Arc::clone(&event_service_clone), game_id, user_id, response, |game, user_id| game.decline_takeback(user_id)).await;
+                    send_if_failed(&tx_clone, response);
                 },
 
                 _ => {
@@ -113,11 +249,57 @@ async fn handle_connection(game_manager: Arc<RwLock<GameManager>>, event_service
 
     println!("{} disconnected", &addr);
     peer_map.lock().await.remove(&addr);
+
+    game_manager.write().await.matchmaking_queue.leave_by_connection(&addr);
+    let spectated_game_ids = game_manager.write().await.connection_manager.remove_ws_connection(&addr);
+    for game_id in spectated_game_ids {
+        publish_spectator_count(Arc::clone(&game_manager), Arc::clone(&event_service), game_id).await;
+    }
+}
+
+fn get_acting_user_id(request: &RequestEnum) -> Option<&str> {
+    match request {
+        RequestEnum::MakeMoveRequest(MakeMoveRequest { user_id, .. })
+        | RequestEnum::ClaimDrawRequest(ClaimDrawRequest { user_id, .. })
+        | RequestEnum::ResignRequest(ResignRequest { user_id, .. })
+        | RequestEnum::OfferDrawRequest(OfferDrawRequest { user_id, .. })
+        | RequestEnum::AcceptDrawRequest(AcceptDrawRequest { user_id, .. })
+        | RequestEnum::DeclineDrawRequest(DeclineDrawRequest { user_id, .. })
+        | RequestEnum::AbortGameRequest(AbortGameRequest { user_id, .. })
+        | RequestEnum::TakebackRequest(TakebackRequest { user_id, .. })
+        | RequestEnum::AcceptTakebackRequest(AcceptTakebackRequest { user_id, .. })
+        | RequestEnum::DeclineTakebackRequest(DeclineTakebackRequest { user_id, .. }) => Some(user_id),
+        _ => None,
+    }
+}
+
+// Answers a single connection directly, e.g. before it is registered.
+fn send_response(unbounded_sender: &Tx, response: &Response) {
+    let response_text = serde_json::to_string(response).unwrap();
+    if let Err(e) = unbounded_sender.unbounded_send(Message::text(response_text)) {
+        println!("Failed to send message to WebSocket connection: {}", e);
+    }
+}
+
+// Replies to the requesting connection when a game action was rejected, accepted
+// actions are already published to every connection of the game.
+fn send_if_failed(unbounded_sender: &Tx, response: Response) {
+    if matches!(response, Response::RequestFailedResponse { .. }) {
+        send_response(unbounded_sender, &response);
+    }
 }
 
 fn text_to_request(text: &str) -> Result<RequestEnum, String> {
+    // requests tagged with their name, e.g. {"ClaimDrawRequest": {...}}
+    if let Ok(request) = from_str::<RequestEnum>(text) {
+        return Ok(request);
+    }
     if let Ok(request) = from_str::<MakeMoveRequest>(text) {
-        return Ok(RequestEnum::MakeMoveRequest(request));
+        // every field that tells a move apart from other requests is optional,
+        // so a move needs either a SAN string or both squares
+        if request.san.is_some() || (request.from.is_some() && request.to.is_some()) {
+            return Ok(RequestEnum::MakeMoveRequest(request));
+        }
     }
     if let Ok(request) = from_str::<AuthorizeWebsocketConnectionRequest>(text) {
         return Ok(RequestEnum::AuthorizeWebsocketConnectionRequest(request));
@@ -138,6 +320,12 @@ async fn authorize(
         let g_m_guard = game_manager.read().await;
         let game = g_m_guard.get_game_by_id(&game_id).await;
         user_color = match game {
+            Ok(game) if !game.is_player(&user_id) => {
+                send_response(&unbounded_sender, &Response::RequestFailedResponse {
+                    message: "Only players can authorize, send a SpectateGameRequest to watch the game".to_string()
+                });
+                return
+            },
             Ok(game) => game.color_by_user_id.get(&user_id).cloned(),
             _ => return
         };
@@ -162,17 +350,155 @@ async fn authorize(
             event_service.read().await.publish(&response).await;
         },
     }
+    send_chat_history(game_manager, event_service, game_id, ChatRoom::Players, address).await;
 }
 
-async fn make_move(
+async fn send_chat_history(
     game_manager: Arc<RwLock<GameManager>>,
     event_service: Arc<RwLock<EventService>>,
     game_id: Uuid,
-    user_id: String,
-    from: String,
-    to: String,
-    promotion_piece: Option<String>,
+    room: ChatRoom,
+    address: SocketAddr,
+) {
+    let messages = game_manager.write().await.get_chat_history(&game_id, &room).await;
+    let response = Response::ChatHistoryResponse { game_id, connection_id: address, room, messages };
+    event_service.read().await.publish(&response).await;
+}
+
+// Stores a chat message and sends it to the sender's room. Spectator connections
+// write to the spectators' room, authorized players to the players' room. The
+// user id of the payload is only trusted for players, whose connection is
+// authorized for it.
+async fn chat(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    request: ChatMessageRequest,
+    address: SocketAddr,
+    unbounded_sender: Tx,
+) {
+    let ChatMessageRequest { game_id, user_id, token, message } = request;
+    let result = {
+        let mut g_m_guard_mut = game_manager.write().await;
+        let connection_manager = &g_m_guard_mut.connection_manager;
+        let room_and_author = if connection_manager.is_spectator_connection_of_game(&game_id, &address) {
+            match token {
+                Some(token) => g_m_guard_mut.token_signer.verify_token(&token, SystemTime::now())
+                    .map(|user_id| (ChatRoom::Spectators, user_id)),
+                None => Ok((ChatRoom::Spectators, ANONYMOUS_SPECTATOR.to_string())),
+            }
+        } else if connection_manager.is_user_connection(&user_id, &address)
+            && g_m_guard_mut.get_game_by_id(&game_id).await.is_ok_and(|game| game.is_player(&user_id)) {
+            Ok((ChatRoom::Players, user_id))
+        } else {
+            Err("Only players and spectators of the game can chat".to_string())
+        };
+
+        match room_and_author.and_then(|(room, author)| ChatMessage::new(game_id, room, author, &message)) {
+            Ok(chat_message) => {
+                if let Err(e) = g_m_guard_mut.add_chat_message(chat_message.clone()).await {
+                    println!("{}", e);
+                }
+                Ok(chat_message)
+            },
+            Err(message) => Err(message),
+        }
+    };
+
+    match result {
+        Ok(ChatMessage { game_id, room, user_id, message, sent_at_ms }) => {
+            let response = Response::ChatMessageResponse { game_id, room, user_id, message, sent_at_ms };
+            event_service.read().await.publish(&response).await;
+        },
+        Err(message) => send_response(&unbounded_sender, &Response::RequestFailedResponse { message }),
+    }
+}
+
+async fn spectate(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    address: SocketAddr,
+    unbounded_sender: Tx,
+) {
+    let (board, result, spectator_count) = {
+        let mut game_manager = game_manager.write().await;
+        let board = match game_manager.get_game_by_id(&game_id).await {
+            Ok(game) => game.get_board().board_to_dict_by_active_color(),
+            Err(_) => {
+                send_response(&unbounded_sender, &Response::RequestFailedResponse {
+                    message: "Wrong game id".to_string()
+                });
+                return
+            },
+        };
+        let result = game_manager.connection_manager.add_spectator_connection(&game_id, address, Arc::new(Mutex::new(unbounded_sender.clone())));
+        (board, result, game_manager.connection_manager.get_spectator_count(&game_id))
+    };
+
+    match result {
+        Ok(message) => {
+            let response = Response::SpectateGameResponse { game_id, connection_id: address, board, spectator_count, message };
+            event_service.read().await.publish(&response).await;
+            event_service.read().await.publish(&Response::SpectatorCountResponse { game_id, spectator_count }).await;
+            send_chat_history(game_manager, event_service, game_id, ChatRoom::Spectators, address).await;
+        },
+        Err(message) => send_response(&unbounded_sender, &Response::RequestFailedResponse { message }),
+    }
+}
+
+// Sends the first page of seeks, later changes follow as they happen.
+async fn subscribe_lobby(game_manager: Arc<RwLock<GameManager>>, addr: SocketAddr, tx: Tx) {
+    let mut g_m_guard_mut = game_manager.write().await;
+    g_m_guard_mut.connection_manager.add_lobby_connection(addr, Arc::new(Mutex::new(tx.clone())));
+    let (seeks, total) = g_m_guard_mut.lobby.get_seeks(&GetSeeksRequest::default());
+    send_response(&tx, &Response::GetSeeksResponse { seeks, total });
+}
+
+// Puts the user of the token in the queue of the time control, with the rating
+// of its category.
+async fn join_queue(game_manager: Arc<RwLock<GameManager>>, token: String, time_control: String, rated: bool, addr: SocketAddr, tx: Tx) {
+    let user_id = match game_manager.read().await.token_signer.verify_token(&token, SystemTime::now()) {
+        Ok(user_id) => user_id,
+        Err(message) => return send_response(&tx, &Response::RequestFailedResponse { message }),
+    };
+    let time_control = match TimeControl::new_from_string(&time_control) {
+        Ok(time_control) => time_control,
+        Err(message) => return send_response(&tx, &Response::RequestFailedResponse { message }),
+    };
+    let category = RatingCategory::new_from_time_control(&time_control);
+    let rating = game_manager.read().await.game_repository.get_rating(&user_id, &category).await
+        .map_or(DEFAULT_RATING, |rating| rating.rating);
+
+    let mut g_m_guard_mut = game_manager.write().await;
+    g_m_guard_mut.connection_manager.add_queue_connection(addr, Arc::new(Mutex::new(tx.clone())));
+    g_m_guard_mut.matchmaking_queue.join(QueueEntry {
+        user_id,
+        connection_id: addr,
+        time_control: time_control.clone(),
+        rated,
+        rating: rating.round() as i32,
+        joined_at: Instant::now(),
+    });
+    let queue_length = g_m_guard_mut.matchmaking_queue.get_queue_length(&time_control, rated);
+    send_response(&tx, &Response::QueueResponse {
+        connection_id: addr,
+        state: "Joined".to_string(),
+        time_control: time_control.to_string(),
+        message: format!("Waiting for an opponent, {} in the queue", queue_length),
+    });
+}
+
+async fn publish_spectator_count(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>, game_id: Uuid) {
+    let spectator_count = game_manager.read().await.connection_manager.get_spectator_count(&game_id);
+    event_service.read().await.publish(&Response::SpectatorCountResponse { game_id, spectator_count }).await;
+}
+
+async fn make_move(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    request: MakeMoveRequest,
 ) -> Response {
+    let MakeMoveRequest { game_id, user_id, from, to, promotion_piece, san } = request;
     // get player color
     let mut user_color: Option<ActiveColor> = None;
     {
@@ -188,9 +514,22 @@ async fn make_move(
 
     // Get mut game, make move
     let mut move_made = false;
+    let draw_offer_expired;
+    let takeback_request_expired;
     {
         match game_manager.write().await.get_mutable_game_by_id(&game_id).await {
             Ok(game) => {
+                if game.get_game_status() != GameStatus::Ongoing {
+                    return Response::RequestFailedResponse {
+                        message: "Game is not ongoing".to_string()
+                    };
+                }
+                // the clock watcher and the deadline sweeper end the game shortly after
+                if game.is_out_of_time() {
+                    return Response::RequestFailedResponse {
+                        message: "Out of time".to_string()
+                    };
+                }
                 match &user_color {
                     Some(color) => {
                         if !color.equals(game.get_active_color()) {
@@ -204,7 +543,19 @@ async fn make_move(
                     },
                 }
 
-                match game.make_move_string(from.clone(), to.clone(), promotion_piece) {
+                let had_draw_offer = game.get_draw_offer().is_some();
+                let had_takeback_request = game.get_takeback_request().is_some();
+                let result = match (san, from, to) {
+                    (Some(san), _, _) => game.make_move_san(san),
+                    (None, Some(from), Some(to)) => game.make_move_string(from, to, promotion_piece),
+                    _ => return Response::RequestFailedResponse {
+                        message: "A move needs either a SAN string or from and to squares".to_string()
+                    },
+                };
+
+                draw_offer_expired = had_draw_offer && game.get_draw_offer().is_none();
+                takeback_request_expired = had_takeback_request && game.get_takeback_request().is_none();
+                match result {
                     true => move_made = true,
                     _ => return Response::RequestFailedResponse {
                         message: "Could not make a move".to_string()
@@ -219,10 +570,22 @@ async fn make_move(
                 board: HashMap::new(),
                 game_status: GameStatus::Aborted,
                 game_end_condition: GameEndCondition::None,
+                san: "".to_string(),
+                white_time_ms: None,
+                black_time_ms: None,
+                spectator_count: 0,
+                white_rating_diff: None,
+                black_rating_diff: None,
             },
         };
     };
 
+    // update game in memory and db, rates a finished rated game
+    {
+        let mut g_m_guard_mut = game_manager.write().await;
+        let _ = g_m_guard_mut.update_game_by_id(&game_id).await;
+    }
+
     // make a move, create a response
     let mut response = Response::RequestFailedResponse {
         message: "Could not make a move".to_string(),
@@ -235,25 +598,32 @@ async fn make_move(
                 match move_made {
                     true => {
                         let board = game.get_board();
+                        let san = board.get_last_move_san().unwrap_or_default();
                         let result = "Made move: ".to_string()
-                            + from.clone().as_str()
-                            + " "
-                            + to.clone().as_str()
+                            + san.as_str()
                             + "\n"
                             + board.board_to_string().as_str();
                         println!("{}", result);
 
+                        let (white_time_ms, black_time_ms) = game.get_remaining_times_ms();
+                        let (white_rating_diff, black_rating_diff) = game.get_rating_diffs().unzip();
                         Response::MakeMoveResponse {
                             game_id,
-                            message: format!("Made move from {} to {}", from, to),
+                            message: format!("Made move {}", san),
                             columns: board.get_columns(),
                             rows: board.get_rows(),
                             board: board.board_to_dict_by_active_color(),
                             game_status: game.get_game_status(),
                             game_end_condition: game.get_game_end_condition(),
+                            san,
+                            white_time_ms,
+                            black_time_ms,
+                            spectator_count: g_m_guard.connection_manager.get_spectator_count(&game_id),
+                            white_rating_diff,
+                            black_rating_diff,
                         }
                     },
-                    false => Response::RequestFailedResponse {
+                    false => return Response::RequestFailedResponse {
                         message: "Could not make a move".to_string()
                     },
                 }
@@ -264,12 +634,337 @@ async fn make_move(
 
         };
     }
-    // update game in memory and db
-    {
+
+    event_service.read().await.publish(&response).await;
+    if draw_offer_expired {
+        let draw_offer_response = Response::DrawOfferResponse {
+            game_id,
+            message: format!("Draw offer expired after a move by {}", user_id),
+            user_id: user_id.clone(),
+            state: "Expired".to_string(),
+        };
+        event_service.read().await.publish(&draw_offer_response).await;
+    }
+    if takeback_request_expired {
+        let takeback_response = Response::TakebackResponse {
+            game_id,
+            message: format!("Takeback request expired after a move by {}", user_id),
+            user_id,
+            state: "Expired".to_string(),
+        };
+        event_service.read().await.publish(&takeback_response).await;
+    }
+    response
+}
+
+// Takes back the requested moves, stores the rolled back board and sends the
+// restored position to every connection of the game.
+async fn accept_takeback(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    user_id: String,
+) -> Response {
+    let board_response = {
         let mut g_m_guard_mut = game_manager.write().await;
+        let spectator_count = g_m_guard_mut.connection_manager.get_spectator_count(&game_id);
+        let board_response = match g_m_guard_mut.get_mutable_game_by_id(&game_id).await {
+            Ok(game) => match game.accept_takeback(&user_id) {
+                Ok(moves_taken_back) => {
+                    let (white_time_ms, black_time_ms) = game.get_remaining_times_ms();
+                    let board = game.get_board();
+                    Response::MakeMoveResponse {
+                        game_id,
+                        message: format!("Took back {} moves", moves_taken_back),
+                        columns: board.get_columns(),
+                        rows: board.get_rows(),
+                        board: board.board_to_dict_by_active_color(),
+                        game_status: game.get_game_status(),
+                        game_end_condition: game.get_game_end_condition(),
+                        san: board.get_last_move_san().unwrap_or_default(),
+                        white_time_ms,
+                        black_time_ms,
+                        spectator_count,
+                        white_rating_diff: None,
+                        black_rating_diff: None,
+                    }
+                },
+                Err(message) => return Response::RequestFailedResponse { message },
+            },
+            Err(_) => return Response::RequestFailedResponse {
+                message: "Wrong game id".to_string()
+            },
+        };
         let _ = g_m_guard_mut.update_game_by_id(&game_id).await;
+        board_response
+    };
+
+    let response = Response::TakebackResponse {
+        game_id,
+        message: format!("{} accepted the takeback", user_id),
+        user_id,
+        state: "Accepted".to_string(),
+    };
+    event_service.read().await.publish(&response).await;
+    event_service.read().await.publish(&board_response).await;
+    response
+}
+
+async fn claim_draw(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    user_id: String,
+) -> Response {
+    let message = format!("Draw claimed by {}", user_id);
+    end_game(game_manager, event_service, game_id, user_id, message, |game, _| game.claim_draw()).await
+}
+
+async fn resign(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    user_id: String,
+) -> Response {
+    let message = format!("{} resigned", user_id);
+    end_game(game_manager, event_service, game_id, user_id, message, |game, user_id| game.resign(user_id)).await
+}
+
+// Applies a game-ending action of a player, persists the game and notifies
+// every connection of the game.
+async fn end_game<F>(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    user_id: String,
+    message: String,
+    action: F,
+) -> Response
+where
+    F: FnOnce(&mut Game, &str) -> Result<GameEndCondition, String>,
+{
+    let response = {
+        let mut g_m_guard_mut = game_manager.write().await;
+        let result = match g_m_guard_mut.get_mutable_game_by_id(&game_id).await {
+            Ok(game) => {
+                if !game.is_player(&user_id) {
+                    return Response::RequestFailedResponse {
+                        message: "Wrong user id".to_string()
+                    };
+                }
+                action(game, &user_id).map(|game_end_condition| (game.get_game_status(), game_end_condition))
+            },
+            Err(_) => return Response::RequestFailedResponse {
+                message: "Wrong game id".to_string()
+            },
+        };
+
+        match result {
+            Ok((game_status, game_end_condition)) => {
+                let _ = g_m_guard_mut.update_game_by_id(&game_id).await;
+                // e.g. the creator aborted the game before anyone joined
+                g_m_guard_mut.lobby.remove_seek(&game_id);
+                g_m_guard_mut.lobby.remove_challenge(&game_id, "Cancelled");
+                let (white_rating_diff, black_rating_diff) = g_m_guard_mut.get_game_by_id(&game_id).await
+                    .map_or((None, None), |game| game.get_rating_diffs().unzip());
+                Response::GameEndedResponse {
+                    game_id,
+                    message,
+                    game_status,
+                    game_end_condition,
+                    white_rating_diff,
+                    black_rating_diff,
+                }
+            },
+            Err(message) => return Response::RequestFailedResponse { message },
+        }
+    };
+
+    event_service.read().await.publish(&response).await;
+    response
+}
+
+// Applies a change of a pending draw offer or takeback request and notifies
+// every connection of the game with the given response.
+async fn update_offer<F>(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    game_id: Uuid,
+    user_id: String,
+    response: Response,
+    action: F,
+) -> Response
+where
+    F: FnOnce(&mut Game, &str) -> Result<(), String>,
+{
+    let result = match game_manager.write().await.get_mutable_game_by_id(&game_id).await {
+        Ok(game) => action(game, &user_id),
+        Err(_) => Err("Wrong game id".to_string()),
+    };
+
+    if let Err(message) = result {
+        return Response::RequestFailedResponse { message };
     }
 
     event_service.read().await.publish(&response).await;
     response
-}
\ No newline at end of file
+}
+
+// Flags players whose clock has run out, even when nobody sends a message.
+async fn run_clock_watcher(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        let game_ids = game_manager.read().await.get_games_out_of_time(Instant::now());
+        for game_id in game_ids {
+            flag_game(Arc::clone(&game_manager), Arc::clone(&event_service), game_id).await;
+        }
+    }
+}
+
+async fn flag_game(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>, game_id: Uuid) {
+    let response = {
+        let mut g_m_guard_mut = game_manager.write().await;
+        let game_end_condition = match g_m_guard_mut.get_mutable_game_by_id(&game_id).await {
+            Ok(game) => match game.flag_if_out_of_time(Instant::now()) {
+                true => game.get_game_end_condition(),
+                false => return,
+            },
+            _ => return,
+        };
+        let _ = g_m_guard_mut.update_game_by_id(&game_id).await;
+        let (white_rating_diff, black_rating_diff) = g_m_guard_mut.get_game_by_id(&game_id).await
+            .map_or((None, None), |game| game.get_rating_diffs().unzip());
+        Response::GameEndedResponse {
+            game_id,
+            message: "Time forfeit".to_string(),
+            game_status: GameStatus::Finished,
+            game_end_condition,
+            white_rating_diff,
+            black_rating_diff,
+        }
+    };
+    println!("Game {} ended on time", game_id);
+    event_service.read().await.publish(&response).await;
+}
+
+// Adjudicates correspondence games whose deadline has passed.
+async fn run_deadline_sweeper(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let adjudicated = game_manager.write().await.adjudicate_missed_deadlines(SystemTime::now()).await;
+        for (game_id, game_end_condition, rating_diffs) in adjudicated {
+            println!("Game {} ended on time", game_id);
+            let (white_rating_diff, black_rating_diff) = rating_diffs.unzip();
+            let response = Response::GameEndedResponse {
+                game_id,
+                message: "Move deadline missed".to_string(),
+                game_status: GameStatus::Finished,
+                game_end_condition,
+                white_rating_diff,
+                black_rating_diff,
+            };
+            event_service.read().await.publish(&response).await;
+        }
+    }
+}
+
+// Pairs players waiting in the matchmaking queue.
+async fn run_matchmaker(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let pairs = game_manager.write().await.matchmaking_queue.find_pairs(Instant::now());
+        for pair in pairs {
+            start_matched_game(Arc::clone(&game_manager), Arc::clone(&event_service), pair).await;
+        }
+    }
+}
+
+// Creates the game of a pair with random colors and tells both players.
+async fn start_matched_game(
+    game_manager: Arc<RwLock<GameManager>>,
+    event_service: Arc<RwLock<EventService>>,
+    (entry, opponent): (QueueEntry, QueueEntry),
+) {
+    let mut game = Game::new(entry.user_id.clone(), Color::Random);
+    game.set_time_control(entry.time_control.clone());
+    game.set_rated(entry.rated);
+    game.set_user(None, Some(opponent.user_id.clone()));
+
+    let result = {
+        let mut g_m_guard_mut = game_manager.write().await;
+        let result = g_m_guard_mut.add_game_to_games(game.clone()).await;
+        if result.is_ok() {
+            for user_id in [&entry.user_id, &opponent.user_id] {
+                let _ = g_m_guard_mut.connection_manager.add_connection(&game.get_game_id(), user_id, None, None);
+            }
+        }
+        result
+    };
+
+    let time_control = entry.time_control.to_string();
+    for queue_entry in [&entry, &opponent] {
+        let response = match &result {
+            Ok((game_id, _)) => Response::MatchFoundResponse {
+                game_id: *game_id,
+                connection_id: queue_entry.connection_id,
+                white_id: game.get_white_id().unwrap_or_default(),
+                black_id: game.get_black_id().unwrap_or_default(),
+                time_control: time_control.clone(),
+                rated: entry.rated,
+            },
+            Err(message) => Response::QueueResponse {
+                connection_id: queue_entry.connection_id,
+                state: "Failed".to_string(),
+                time_control: time_control.clone(),
+                message: format!("Could not create the game: {}", message),
+            },
+        };
+        event_service.read().await.publish(&response).await;
+    }
+    if result.is_ok() {
+        println!("Matched {} and {} in game {}", entry.user_id, opponent.user_id, game.get_game_id());
+    }
+}
+
+// Seconds to make one of the first two moves before the game is aborted,
+// configured with ABORT_WINDOW_SECONDS.
+fn abort_window() -> Duration {
+    let seconds = env::var("ABORT_WINDOW_SECONDS").ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_ABORT_WINDOW_SECONDS);
+    Duration::from_secs(seconds)
+}
+
+async fn run_abort_watcher(game_manager: Arc<RwLock<GameManager>>, event_service: Arc<RwLock<EventService>>, window: Duration) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let game_ids = game_manager.read().await.get_games_to_abort(Instant::now(), window);
+        for game_id in game_ids {
+            let response = {
+                let mut g_m_guard_mut = game_manager.write().await;
+                let aborted = match g_m_guard_mut.get_mutable_game_by_id(&game_id).await {
+                    Ok(game) => game.abort_if_first_move_missed(Instant::now(), window),
+                    Err(_) => false,
+                };
+                if !aborted {
+                    continue;
+                }
+                let _ = g_m_guard_mut.update_game_by_id(&game_id).await;
+                Response::GameEndedResponse {
+                    game_id,
+                    message: "Game aborted, the first move was not made in time".to_string(),
+                    game_status: GameStatus::Aborted,
+                    game_end_condition: GameEndCondition::None,
+                    white_rating_diff: None,
+                    black_rating_diff: None,
+                }
+            };
+            println!("Game {} aborted", game_id);
+            event_service.read().await.publish(&response).await;
+        }
+    }
+}